@@ -1,6 +1,10 @@
 use bpaf::Bpaf;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::scenario::parser::ParamValue;
 
 /// Argument of the program, resulting from an aggregation of default values,
 /// optional command line options, and optional configuration file.
@@ -22,13 +26,26 @@ pub struct Args {
     pub uds_commands: Option<Vec<Vec<u8>>>,
     /// Scenario to execute
     pub scenario: Option<String>,
+    /// Scenario parameters values
+    pub parameters: BTreeMap<String, ParamValue>,
+    /// Maximum duration of the whole scenario
+    pub scenario_timeout: Option<Duration>,
+    /// File where the scenario progress is written
+    pub checkpoint: Option<String>,
+    /// Checkpoint file to resume an interrupted scenario from
+    pub resume: Option<String>,
+    /// Shared library computing the SecurityAccess keys
+    pub seedkey_library: Option<String>,
+    /// Period of the TesterPresent sent in the non-default sessions
+    pub tester_present: Option<Duration>,
 }
 
 /// Parse commandline
 ///
 /// Parse commandline arguments, augment with a potential commandline provided
-/// configuration file, and return the result.
-pub fn get_args() -> Args {
+/// configuration file, and return the result, or a description of the invalid
+/// configuration.
+pub fn get_args() -> Result<Args, String> {
     _get_args()
 }
 
@@ -54,7 +71,7 @@ fn parse_hex_u8_multiple(ins: &str) -> Option<(u8, usize)> {
 fn parse_u16(ins: &str) -> Option<u16> {
     ins.parse::<u16>()
         .or_else(|_| u16::from_str_radix(ins, 16))
-        .or_else(|_| u16::from_str_radix(&ins[2..], 16))
+        .or_else(|_| u16::from_str_radix(ins.get(2..).unwrap_or_default(), 16))
         .ok()
 }
 
@@ -79,6 +96,23 @@ fn parse_uds_commands(ins: Vec<&str>) -> Option<Vec<Vec<u8>>> {
         .collect::<Option<Vec<Vec<u8>>>>()
 }
 
+fn parse_socket_addr(ins: Option<String>, name: &str) -> Result<SocketAddr, String> {
+    let ins = ins.unwrap_or_default();
+    ins.parse()
+        .map_err(|err| format!("{name} \"{ins}\" is not a socket address: {err}"))
+}
+
+fn split_parameter(ins: &str) -> Option<(&str, &str)> {
+    ins.split_once('=').filter(|(name, _)| !name.is_empty())
+}
+
+fn parse_parameter(ins: &str) -> Result<(String, ParamValue), String> {
+    let (name, value) =
+        split_parameter(ins).ok_or(format!("parameter \"{ins}\" is not of name=value form"))?;
+    let value = ParamValue::parse(value).map_err(|err| format!("parameter {name}: {err}"))?;
+    Ok((name.to_string(), value))
+}
+
 #[derive(Debug, Clone, Bpaf, Deserialize, Default)]
 #[bpaf(options)]
 struct Options {
@@ -122,6 +156,37 @@ struct Options {
     /// A scenario is a list of uds command and special shortcuts, like TransferDownload
     /// Can be several scenarii separated by a comma, such as "--scenario dtc0a.yaml,reprog_fd01.yaml"
     scenario: Option<String>,
+    #[bpaf(long("set"), argument("NAME=VALUE"), guard(|x| x.iter().all(|s| split_parameter(s).is_some()), "parameters should be of name=value form"))]
+    /// Scenario parameter, assigned to an evalexpr variable before the scenario
+    /// starts. The value is an integer, a list of bytes or a string, such as
+    /// "--set did=0xf190", "--set key=[0x01, 0x02]" or "--set vin=VF1R".
+    /// Can be repeated, and overrides the values of the --vars file.
+    #[serde(default)]
+    set: Vec<String>,
+    #[bpaf(long)]
+    /// Optional yaml file of scenario parameters, such as "vin: VF1R".
+    vars: Option<String>,
+    #[bpaf(long, argument("MS"))]
+    /// Optional maximum duration of the whole scenario, in milliseconds.
+    /// The scenario fails with a timeout error once it is elapsed.
+    scenario_timeout: Option<u64>,
+    #[bpaf(long, argument("FILE"))]
+    /// Optional json file where the scenario progress is written after each
+    /// top-level step, such as "--checkpoint reprog.json".
+    checkpoint: Option<String>,
+    #[bpaf(long, argument("FILE"))]
+    /// Optional checkpoint file of an interrupted scenario. The completed steps
+    /// are skipped, and the evalexpr variables restored. The progress is then
+    /// written to the same file, unless --checkpoint is given.
+    resume: Option<String>,
+    #[bpaf(long, argument("FILE"))]
+    /// Optional shared library exposing a GenerateKeyEx function, computing the
+    /// keys of the SecurityAccess steps, such as "--seedkey-library libkey.so".
+    seedkey_library: Option<String>,
+    #[bpaf(long, argument("MS"))]
+    /// Optional period of a TesterPresent keep-alive, in milliseconds, sent
+    /// while a DiagnosticSession step has switched to a non-default session.
+    tester_present: Option<u64>,
     /// UDS commands to launch, such as "10 03" "22 02" or "22 02 FF*12"
     #[bpaf(positional("UDS commands"), guard(|x| parse_uds_commands(x.iter().map(|s| &**s).collect()).is_some(), "commands should be space separated quoted strings of space separated double-hexa-nibbles"))]
     uds_commands: Vec<String>,
@@ -141,10 +206,17 @@ fn override_opts(src: Options, overrider: Options) -> Options {
         configfile: overrider.configfile.or(src.configfile),
         uds_commands,
         scenario: overrider.scenario.or(src.scenario),
+        set: [src.set, overrider.set].concat(),
+        vars: overrider.vars.or(src.vars),
+        scenario_timeout: overrider.scenario_timeout.or(src.scenario_timeout),
+        checkpoint: overrider.checkpoint.or(src.checkpoint),
+        resume: overrider.resume.or(src.resume),
+        seedkey_library: overrider.seedkey_library.or(src.seedkey_library),
+        tester_present: overrider.tester_present.or(src.tester_present),
     }
 }
 
-pub fn _get_args() -> Args {
+pub fn _get_args() -> Result<Args, String> {
     let default_opts = Options {
         local_diag_socket: Some("192.168.11.10:0".to_string()),
         remote_diag_socket: Some("192.168.11.53:13400".to_string()),
@@ -155,24 +227,42 @@ pub fn _get_args() -> Args {
         configfile: None,
         uds_commands: vec![],
         scenario: None,
+        set: vec![],
+        vars: None,
+        scenario_timeout: None,
+        checkpoint: None,
+        resume: None,
+        seedkey_library: None,
+        tester_present: None,
     };
     let commandline_opts = options().run();
     let filename_opts = match &commandline_opts.configfile {
-        Some(filename) => configfile::read_file(filename),
+        Some(filename) => configfile::read_file(filename)?,
         None => Options::default(),
     };
     let opts = override_opts(default_opts, filename_opts);
     let opts = override_opts(opts, commandline_opts);
 
-    let local_addr = opts.local_diag_socket.unwrap().parse().unwrap();
-    let remote_addr = opts.remote_diag_socket.unwrap().parse().unwrap();
-    let broadcast_addr = opts.broadcast_diag_socket.unwrap().parse().unwrap();
+    let local_addr = parse_socket_addr(opts.local_diag_socket, "local_diag_socket")?;
+    let remote_addr = parse_socket_addr(opts.remote_diag_socket, "remote_diag_socket")?;
+    let broadcast_addr = parse_socket_addr(opts.broadcast_diag_socket, "broadcast_diag_socket")?;
     let discover = opts.discover;
-    let doip_la = parse_u16(&opts.doip_local_addr.unwrap_or("0x0e80".to_string())).unwrap();
-    let doip_ta = parse_u16(&opts.doip_target_addr.unwrap_or("0x00ed".to_string())).unwrap();
+    let doip_la = parse_u16(&opts.doip_local_addr.unwrap_or("0x0e80".to_string()))
+        .ok_or("doip_local_addr must be of 0xXYUV form, like 0x00ed")?;
+    let doip_ta = parse_u16(&opts.doip_target_addr.unwrap_or("0x00ed".to_string()))
+        .ok_or("doip_target_addr must be of 0xXYUV form, like 0x00ed")?;
     let uds_commands = parse_uds_commands(opts.uds_commands.iter().map(|s| &**s).collect());
     let scenario = opts.scenario;
-    Args {
+    let mut parameters = match &opts.vars {
+        Some(filename) => configfile::read_vars(filename)?,
+        None => BTreeMap::new(),
+    };
+    for set in opts.set.iter() {
+        let (name, value) = parse_parameter(set)?;
+        parameters.insert(name, value);
+    }
+    let scenario_timeout = opts.scenario_timeout.map(Duration::from_millis);
+    Ok(Args {
         local_addr,
         remote_addr,
         broadcast_addr,
@@ -181,18 +271,29 @@ pub fn _get_args() -> Args {
         doip_ta,
         uds_commands,
         scenario,
-    }
+        parameters,
+        scenario_timeout,
+        checkpoint: opts.checkpoint,
+        resume: opts.resume,
+        seedkey_library: opts.seedkey_library,
+        tester_present: opts.tester_present.map(Duration::from_millis),
+    })
 }
 
 mod configfile {
-    use super::Options;
+    use super::{Options, ParamValue};
     use serde_yaml::from_reader;
+    use std::collections::BTreeMap;
+
+    pub(super) fn read_file(filename: &str) -> Result<Options, String> {
+        let f = std::fs::File::open(filename)
+            .map_err(|err| format!("Can't open configuration file {filename}: {err}"))?;
+        from_reader(f).map_err(|err| format!("Can't parse configuration file {filename}: {err}"))
+    }
 
-    pub(super) fn read_file(filename: &str) -> Options {
+    pub(super) fn read_vars(filename: &str) -> Result<BTreeMap<String, ParamValue>, String> {
         let f = std::fs::File::open(filename)
-            .inspect_err(|err| println!("Can't open configuration file {filename}: {err}"))
-            .unwrap();
-        let opts: Options = from_reader(f).unwrap();
-        opts
+            .map_err(|err| format!("Can't open variables file {filename}: {err}"))?;
+        from_reader(f).map_err(|err| format!("Can't parse variables file {filename}: {err}"))
     }
 }
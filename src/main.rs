@@ -1,7 +1,12 @@
 use doip_rw_tokio::DoIpUdpConnection;
+use scenario::checkpoint::Checkpoint;
+use scenario::error::ScenarioError;
+use scenario::main::Outcome;
 use scenario::parser::Step;
 use std::io::{self};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use tokio::net::UdpSocket;
 use tokio::time::{self, Duration};
 
@@ -11,6 +16,55 @@ mod scenario;
 #[cfg(test)]
 mod tests;
 
+/// Exit codes of the process, one per outcome.
+mod exit_code {
+    /// The scenario finished, all assertions passed
+    pub const SUCCESS: u8 = 0;
+    /// The scenario finished, but some assertions failed
+    pub const ASSERTIONS_FAILED: u8 = 1;
+    /// The scenario was aborted by a step, such as AbortIfNrc
+    pub const ABORTED: u8 = 2;
+    /// The ECU replied with an NRC or an unexpected reply
+    pub const ECU_REPLY: u8 = 3;
+    /// The DoIP connection to the ECU failed or was lost
+    pub const NETWORK: u8 = 4;
+    /// The configuration or the scenario is invalid
+    pub const CONFIG: u8 = 5;
+    /// A file couldn't be read or written
+    pub const IO: u8 = 6;
+    /// A step or the whole scenario took longer than its timeout, or the ECU
+    /// didn't reply in time
+    pub const TIMEOUT: u8 = 7;
+}
+
+fn scenario_exit_code(res: &Result<Outcome, ScenarioError>) -> u8 {
+    match res {
+        Ok(Outcome::Success) => exit_code::SUCCESS,
+        Ok(Outcome::AssertionsFailed(_)) => exit_code::ASSERTIONS_FAILED,
+        Ok(Outcome::Aborted) => exit_code::ABORTED,
+        Err(err) => match err {
+            ScenarioError::Nrc(_)
+            | ScenarioError::UnexpectedUdsMessage(_)
+            | ScenarioError::UdsError(_) => exit_code::ECU_REPLY,
+            ScenarioError::NetworkConnectorDead | ScenarioError::RoutingActivationFailed => {
+                exit_code::NETWORK
+            }
+            ScenarioError::Io(_) => exit_code::IO,
+            ScenarioError::Timeout(_) | ScenarioError::NoReply(_) => exit_code::TIMEOUT,
+            ScenarioError::EvalExpr(_, _)
+            | ScenarioError::ScenarioFile(_, _)
+            | ScenarioError::Parse(_, _)
+            | ScenarioError::IncludeCycle(_)
+            | ScenarioError::DuplicateProcedure(_)
+            | ScenarioError::InvalidCall(_, _)
+            | ScenarioError::MissingParameter(_)
+            | ScenarioError::InvalidCheckpoint(_)
+            | ScenarioError::SeedKey(_)
+            | ScenarioError::InvalidStep(_, _) => exit_code::CONFIG,
+        },
+    }
+}
+
 async fn discover_doip_entities(
     local_addr: SocketAddr,
     broadcast_addr: SocketAddr,
@@ -45,9 +99,15 @@ async fn discover_doip_entities(
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     use log::LevelFilter;
-    let args = argparse::get_args();
+    let args = match argparse::get_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Invalid configuration: {err}");
+            return ExitCode::from(exit_code::CONFIG);
+        }
+    };
 
     if let Some(commands) = &args.uds_commands {
         if args.uds_commands.is_some() && !commands.is_empty() {
@@ -62,17 +122,19 @@ async fn main() {
     }
 
     if args.discover {
-        discover_doip_entities(args.local_addr, args.broadcast_addr)
-            .await
-            .unwrap();
+        if let Err(err) = discover_doip_entities(args.local_addr, args.broadcast_addr).await {
+            eprintln!("DoIP entities discovery failed: {err}");
+            return ExitCode::from(exit_code::NETWORK);
+        }
     }
 
-    let mut steps = vec![];
+    let mut scen = scenario::parser::Scenario::default();
     if let Some(commands) = args.uds_commands {
         if !commands.is_empty() {
             for command in commands {
-                steps.push(Step::RawUds(scenario::parser::RawUds {
+                scen.steps.push(Step::RawUds(scenario::parser::RawUds {
                     data: scenario::parser::RawBytes::Bytes(command),
+                    options: scenario::parser::StepOptions::default(),
                 }));
             }
         }
@@ -80,20 +142,43 @@ async fn main() {
 
     if let Some(scenario_filenames) = args.scenario {
         for scenario_filename in scenario_filenames.split(',') {
-            let mut this_steps = scenario::parser::read_scenario(scenario_filename);
-            steps.append(&mut this_steps);
+            if let Err(err) = scenario::parser::read_scenario(scenario_filename)
+                .and_then(|this_scenario| scen.append(this_scenario))
+            {
+                eprintln!("Scenario can't be loaded: {err}");
+                return ExitCode::from(scenario_exit_code(&Err(err)));
+            }
         }
     }
 
-    scenario::main::scenario(
+    let resume = match args.resume.as_deref().map(Path::new).map(Checkpoint::read) {
+        Some(Ok(checkpoint)) => Some(checkpoint),
+        Some(Err(err)) => {
+            eprintln!("Scenario can't be resumed: {err}");
+            return ExitCode::from(scenario_exit_code(&Err(err)));
+        }
+        None => None,
+    };
+    let settings = scenario::main::Settings {
+        timeout: args.scenario_timeout,
+        checkpoint_file: args.checkpoint.or(args.resume).map(PathBuf::from),
+        resume,
+        seedkey_library: args.seedkey_library.map(PathBuf::from),
+        tester_present: args.tester_present,
+    };
+
+    let res = scenario::main::scenario(
         args.local_addr,
         args.remote_addr,
         args.doip_la,
         args.doip_ta,
-        steps,
+        scen,
+        args.parameters,
+        settings,
     )
-    .await
-    .unwrap_or_else(|err| {
-        panic!("Scenario aborted due to an error: {err}");
-    });
+    .await;
+    if let Err(err) = &res {
+        eprintln!("Scenario aborted due to an error: {err}");
+    }
+    ExitCode::from(scenario_exit_code(&res))
 }
@@ -1,5 +1,8 @@
+pub mod checkpoint;
 mod doip_ops;
+mod dtc;
 pub mod error;
 mod executor;
 pub mod main;
 pub mod parser;
+mod seedkey;
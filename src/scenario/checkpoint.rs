@@ -0,0 +1,92 @@
+use evalexpr::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use super::error::ScenarioError;
+use super::parser::Scenario;
+
+/// Progress of a scenario, written after each top-level step, so that an
+/// interrupted scenario can be resumed.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    /// Hash of the scenario, which must be the same to be resumed
+    pub scenario_hash: u64,
+    /// Index of the next top-level step to execute
+    pub step_index: usize,
+    /// Iterations completed by the top-level loop in progress, if any
+    pub loop_counter: Option<usize>,
+    /// Evalexpr variables
+    pub variables: BTreeMap<String, Variable>,
+}
+
+/// Evalexpr variable value.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum Variable {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Tuple(Vec<Variable>),
+    Empty,
+}
+
+impl Checkpoint {
+    pub fn read(filename: &Path) -> Result<Checkpoint, ScenarioError> {
+        let name = filename.display().to_string();
+        let f = File::open(filename)
+            .map_err(|err| ScenarioError::InvalidCheckpoint(format!("{name}: {err}")))?;
+        serde_json::from_reader(f)
+            .map_err(|err| ScenarioError::InvalidCheckpoint(format!("{name}: {err}")))
+    }
+
+    /// Write the checkpoint to a temporary file first, so that an interruption
+    /// never leaves a truncated checkpoint.
+    pub fn write(&self, filename: &Path) -> Result<(), ScenarioError> {
+        let tmp = filename.with_extension("tmp");
+        let f = File::create(&tmp)?;
+        serde_json::to_writer_pretty(&f, self).map_err(io::Error::from)?;
+        f.sync_all()?;
+        fs::rename(&tmp, filename)?;
+        Ok(())
+    }
+}
+
+/// FNV-1a hash of the scenario, as serialized, so that it is the same from one
+/// build of diagtool to another.
+pub fn scenario_hash(scenario: &Scenario) -> Result<u64, ScenarioError> {
+    let yaml = serde_yaml::to_string(scenario).map_err(io::Error::other)?;
+    Ok(yaml.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    }))
+}
+
+impl From<&Value> for Variable {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Int(i) => Variable::Int(*i),
+            Value::Float(f) => Variable::Float(*f),
+            Value::String(s) => Variable::String(s.clone()),
+            Value::Boolean(b) => Variable::Boolean(*b),
+            Value::Tuple(values) => Variable::Tuple(values.iter().map(Variable::from).collect()),
+            Value::Empty => Variable::Empty,
+        }
+    }
+}
+
+impl From<Variable> for Value {
+    fn from(variable: Variable) -> Self {
+        match variable {
+            Variable::Int(i) => Value::Int(i),
+            Variable::Float(f) => Value::Float(f),
+            Variable::String(s) => Value::String(s),
+            Variable::Boolean(b) => Value::Boolean(b),
+            Variable::Tuple(variables) => {
+                Value::Tuple(variables.into_iter().map(Value::from).collect())
+            }
+            Variable::Empty => Value::Empty,
+        }
+    }
+}
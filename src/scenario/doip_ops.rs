@@ -1,12 +1,28 @@
-use std::{io, io::Cursor, net::SocketAddr, time::Duration};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{io, io::Cursor, time::Duration};
+use tokio::net::UdpSocket;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
 
 use super::error::ScenarioError;
 use doip_rw::{message::UdsBuffer, LogicalAddress};
 
-use uds_rw::{uds_write, UdsMessage};
+use log::debug;
+use uds_rw::{message::RawUds, uds_write, UdsMessage};
 
 use doip_rw_tokio::{DoIpCnxError, DoIpTcpConnection, Timings};
 
+/// Services with a subfunction, the bit 7 of which is the
+/// suppressPosRspMsgIndicationBit.
+const SUBFUNCTION_SERVICES: [u8; 12] = [
+    0x10, 0x11, 0x27, 0x28, 0x29, 0x2c, 0x31, 0x3e, 0x83, 0x85, 0x86, 0x87,
+];
+
+/// Tell if the suppressPosRspMsgIndicationBit of a request is set.
+pub fn suppress_positive_response(req: &[u8]) -> bool {
+    matches!(req, [sid, subfunction, ..]
+        if SUBFUNCTION_SERVICES.contains(sid) && subfunction & 0x80 != 0)
+}
+
 #[derive(Debug)]
 pub enum ScenarioMessage {
     Uds(UdsMessage),
@@ -15,6 +31,15 @@ pub enum ScenarioMessage {
     DisconnectReconnectReq,
     NotifyNewDoIpCnx,
     NotifyDoIpCnxRoutingAck,
+    /// Start sending TesterPresent at the given period, or stop if None
+    KeepAlive(Option<Duration>),
+    /// Reconnect until the routing activation succeeds, such as after an ECU
+    /// reset
+    ReconnectReq {
+        max_wait: Duration,
+        wait_announcement: bool,
+    },
+    NotifyReconnectFailed(ScenarioError),
 }
 
 pub struct DoIpConnection {
@@ -68,6 +93,12 @@ impl DoIpConnection {
             DisconnectReconnectReq => self.reconnect().await,
             NotifyNewDoIpCnx => Ok(()),
             NotifyDoIpCnxRoutingAck => Ok(()),
+            KeepAlive(_) => Ok(()),
+            ReconnectReq {
+                max_wait,
+                wait_announcement,
+            } => self.reconnect_until(ta, max_wait, wait_announcement).await,
+            NotifyReconnectFailed(_) => Ok(()),
         }
     }
 
@@ -84,31 +115,120 @@ impl DoIpConnection {
         }
     }
 
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
     pub async fn reconnect(&mut self) -> Result<(), ScenarioError> {
         if let Some(old) = self.connection.take() {
             drop(old);
-            let connection = DoIpTcpConnection::connect_doip_tcp(
-                self.local_addr,
-                self.remote_addr,
-                self.la,
-                self.timings.clone(),
-            )
-            .await?;
-            self.notify_new_cnx = true;
-            self.notify_doip_routed = true;
-            self.connection = Some(connection);
+            self.connect_again().await?;
+        }
+        Ok(())
+    }
+
+    /// Reconnect until the routing activation succeeds or max_wait is
+    /// elapsed, optionally once the DoIP entity ta announced itself. An
+    /// io::ErrorKind::TimedOut error is returned once max_wait is elapsed.
+    pub async fn reconnect_until(
+        &mut self,
+        ta: LogicalAddress,
+        max_wait: Duration,
+        wait_announcement: bool,
+    ) -> Result<(), ScenarioError> {
+        let deadline = Instant::now() + max_wait;
+        drop(self.connection.take());
+        if wait_announcement {
+            let ip = match self.local_addr {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            };
+            let socket = UdpSocket::bind(SocketAddr::new(ip, UDP_DISCOVERY)).await?;
+            let announcement = wait_vehicle_announcement(&socket, self.remote_addr.ip(), ta);
+            time::timeout_at(deadline, announcement)
+                .await
+                .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        }
+        loop {
+            match time::timeout_at(deadline, self.connect_again()).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(err)) => debug!("DoIp reconnection failed: {err}"),
+                Err(_) => return Err(io::Error::from(io::ErrorKind::TimedOut).into()),
+            }
+            time::sleep_until(deadline.min(Instant::now() + RECONNECT_DELAY)).await;
         }
+    }
+
+    async fn connect_again(&mut self) -> Result<(), ScenarioError> {
+        let connection = DoIpTcpConnection::connect_doip_tcp(
+            self.local_addr,
+            self.remote_addr,
+            self.la,
+            self.timings.clone(),
+        )
+        .await?;
+        self.notify_new_cnx = true;
+        self.notify_doip_routed = true;
+        self.connection = Some(connection);
         Ok(())
     }
 }
 
+/// Delay between two reconnection attempts
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// UDP port of the vehicle announcements
+const UDP_DISCOVERY: u16 = 13400;
+
+/// Payload type of the vehicle announcements and identification responses
+const VEHICLE_ANNOUNCEMENT: u16 = 0x0004;
+
+/// Wait for a vehicle announcement, broadcast by a DoIP entity when it starts,
+/// sent from the remote_ip address or announcing the ta logical address.
+async fn wait_vehicle_announcement(
+    socket: &UdpSocket,
+    remote_ip: IpAddr,
+    ta: LogicalAddress,
+) -> Result<(), ScenarioError> {
+    let mut buf = [0u8; 64];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        match announced_address(&buf[..len]) {
+            Some(la) if la == ta || from.ip() == remote_ip => {
+                debug!("Vehicle announcement received from {from}, logical address {la:#06x}");
+                return Ok(());
+            }
+            Some(la) => {
+                debug!("Dropping vehicle announcement of {from}, logical address {la:#06x}")
+            }
+            None => debug!("Dropping DoIP UDP message of {from}"),
+        }
+    }
+}
+
+/// Logical address of a vehicle announcement, or None for another message.
+fn announced_address(msg: &[u8]) -> Option<LogicalAddress> {
+    // Header of protocol version, its inverse, payload type and payload
+    // length, then VIN and logical address
+    match msg {
+        [version, inverse, pt0, pt1, _, _, _, _, payload @ ..]
+            if *version == !*inverse
+                && u16::from_be_bytes([*pt0, *pt1]) == VEHICLE_ANNOUNCEMENT
+                && payload.len() >= 19 =>
+        {
+            Some(u16::from_be_bytes([payload[17], payload[18]]))
+        }
+        _ => None,
+    }
+}
+
 async fn doip_scenario_receive(cnx: &mut DoIpConnection) -> Result<ScenarioMessage, ScenarioError> {
     let buffer_holder = &mut cnx.receive_buffer_holder;
     loop {
         let msg = cnx
             .connection
             .as_mut()
-            .unwrap() // Here connection cannot be None
+            .ok_or(ScenarioError::NetworkConnectorDead)?
             .receive_message(|_, size| {
                 let mut v = buffer_holder.take().unwrap();
                 v.resize(size, 0);
@@ -143,7 +263,7 @@ async fn doip_scenario_receive(cnx: &mut DoIpConnection) -> Result<ScenarioMessa
 async fn send_alive_check_rsp(cnx: &mut DoIpConnection) -> Result<(), ScenarioError> {
     cnx.connection
         .as_mut()
-        .unwrap() // Cannot be None
+        .ok_or(ScenarioError::NetworkConnectorDead)?
         .send_alive_check_response(cnx.la)
         .await?;
     Ok(())
@@ -159,7 +279,9 @@ async fn send_uds(
         .map_err(|_| ScenarioError::UnexpectedUdsMessage(uds_req))?;
 
     let ack = doip_rw_tokio::send_uds(
-        cnx.connection.as_mut().unwrap(), // Cannot be None
+        cnx.connection
+            .as_mut()
+            .ok_or(ScenarioError::NetworkConnectorDead)?,
         ta,
         UdsBuffer::Borrowed(&uds_bytes),
         |_, size| {
@@ -199,6 +321,127 @@ impl From<DoIpCnxError> for ScenarioError {
     }
 }
 
+/// Time after which a request without any reply is considered lost, and no
+/// longer holds back the TesterPresent keep-alive.
+const LOST_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time after which no NRC to a request with a suppressed positive response,
+/// such as the TesterPresent of the keep-alive, is expected anymore, longer
+/// than the P2 of the ECUs.
+const SUPPRESSED_REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// TesterPresent keep-alive, sent by the network task in between the requests
+/// of the scenario.
+#[derive(Default)]
+pub struct KeepAlive {
+    interval: Option<Interval>,
+    /// Service of the request of the scenario awaiting its reply, with the
+    /// time after which the request is considered lost
+    request_pending: Option<(u8, Instant)>,
+    /// Time a TesterPresent was sent, its reply, if any, being dropped
+    tester_present_sent: Option<Instant>,
+}
+
+impl KeepAlive {
+    /// TesterPresent with the suppressPosRspMsgIndicationBit set
+    const TESTER_PRESENT: [u8; 2] = [0x3e, 0x80];
+
+    pub fn set_period(&mut self, period: Option<Duration>) {
+        self.interval = period.map(|period| {
+            let mut interval = time::interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// Wait for the next TesterPresent to send, skipping the periods where a
+    /// request of the scenario is awaiting its reply.
+    pub async fn tick(&mut self) -> ScenarioMessage {
+        loop {
+            match self.interval.as_mut() {
+                Some(interval) => interval.tick().await,
+                None => std::future::pending().await,
+            };
+            match self.request_pending {
+                Some((_, deadline)) if Instant::now() < deadline => continue,
+                _ => {
+                    self.request_pending = None;
+                    self.tester_present_sent = Some(Instant::now());
+                    let data = Self::TESTER_PRESENT.to_vec();
+                    return ScenarioMessage::Uds(UdsMessage::RawUds(RawUds { data }));
+                }
+            }
+        }
+    }
+
+    /// Track a message sent by the scenario.
+    pub fn on_request(&mut self, msg: &ScenarioMessage) {
+        match msg {
+            ScenarioMessage::Uds(uds) => {
+                let mut req: Vec<u8> = vec![];
+                let suppressed =
+                    uds_write(&mut req, uds).is_ok() && suppress_positive_response(&req);
+                if req.first() == Some(&0x3e) {
+                    // The next TesterPresent reply belongs to the scenario
+                    self.tester_present_sent = None;
+                }
+                // Only an NRC may follow a request with a suppressed response,
+                // within the P2 of the ECU
+                let timeout = if suppressed {
+                    SUPPRESSED_REPLY_TIMEOUT
+                } else {
+                    LOST_REQUEST_TIMEOUT
+                };
+                self.request_pending = Some((uds.into(), Instant::now() + timeout));
+            }
+            ScenarioMessage::DisconnectReconnectReq | ScenarioMessage::ReconnectReq { .. } => {
+                self.request_pending = None
+            }
+            _ => {}
+        }
+    }
+
+    /// Track a message received from the ECU, returning false if it is a reply
+    /// to the keep-alive, which the scenario mustn't see.
+    pub fn on_reply(&mut self, msg: &ScenarioMessage) -> bool {
+        let ScenarioMessage::Uds(uds) = msg else {
+            return true;
+        };
+        let mut reply: Vec<u8> = vec![];
+        if uds_write(&mut reply, uds).is_err() {
+            return true;
+        }
+        let reply_sid = match reply[..] {
+            [0x7f, sid, ..] => sid,
+            [sid, ..] => sid & !0x40,
+            [] => return true,
+        };
+        let pending_sid = self.request_pending.map(|(sid, _)| sid);
+        if reply_sid == 0x3e
+            && pending_sid != Some(0x3e)
+            && self
+                .tester_present_sent
+                .take()
+                .is_some_and(|since| since.elapsed() < SUPPRESSED_REPLY_TIMEOUT)
+        {
+            debug!("Dropping reply to TesterPresent: {uds}");
+            return false;
+        }
+        if let [0x7f, _, 0x78] = reply[..] {
+            // responsePending, the reply is still to come, even if suppressed
+            self.request_pending =
+                pending_sid.map(|sid| (sid, Instant::now() + LOST_REQUEST_TIMEOUT));
+        } else {
+            self.request_pending = None;
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::io::Write;
@@ -231,4 +474,100 @@ mod test {
         let _ = f2.await;
         let _ = f1.await;
     }
+
+    fn vehicle_announcement(la: u16) -> Vec<u8> {
+        let mut msg = vec![0x02, 0xfd, 0x00, 0x04, 0x00, 0x00, 0x00, 0x20];
+        msg.extend_from_slice(b"VF1AAAAAAAAAAAAAA");
+        msg.extend_from_slice(&la.to_be_bytes());
+        msg.extend_from_slice(&[0; 13]);
+        msg
+    }
+
+    #[tokio::test]
+    async fn wait_vehicle_announcement() {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let ecu = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let other_ip = "127.0.0.2".parse().unwrap();
+        let timeout = std::time::Duration::from_millis(200);
+
+        // Neither from the remote address, nor of the target
+        ecu.send_to(&[0x02, 0xfd, 0x00, 0x01, 0, 0, 0, 0], addr)
+            .await
+            .unwrap();
+        ecu.send_to(&vehicle_announcement(0x0078), addr)
+            .await
+            .unwrap();
+        let wait = super::wait_vehicle_announcement(&socket, other_ip, 0x0077);
+        assert!(tokio::time::timeout(timeout, wait).await.is_err());
+
+        ecu.send_to(&vehicle_announcement(0x0077), addr)
+            .await
+            .unwrap();
+        let wait = super::wait_vehicle_announcement(&socket, other_ip, 0x0077);
+        assert!(matches!(
+            tokio::time::timeout(timeout, wait).await,
+            Ok(Ok(()))
+        ));
+
+        ecu.send_to(&vehicle_announcement(0x0078), addr)
+            .await
+            .unwrap();
+        let remote_ip = ecu.local_addr().unwrap().ip();
+        let wait = super::wait_vehicle_announcement(&socket, remote_ip, 0x0077);
+        assert!(matches!(
+            tokio::time::timeout(timeout, wait).await,
+            Ok(Ok(()))
+        ));
+    }
+
+    fn uds(data: &[u8]) -> super::ScenarioMessage {
+        let data = data.to_vec();
+        super::ScenarioMessage::Uds(super::UdsMessage::RawUds(super::RawUds { data }))
+    }
+
+    #[tokio::test]
+    async fn keepalive_tester_present_nrc() {
+        let mut keepalive = super::KeepAlive {
+            tester_present_sent: Some(super::Instant::now()),
+            ..Default::default()
+        };
+        assert!(!keepalive.on_reply(&uds(&[0x7f, 0x3e, 0x11])));
+        assert!(keepalive.tester_present_sent.is_none());
+    }
+
+    #[tokio::test]
+    async fn keepalive_scenario_tester_present() {
+        let mut keepalive = super::KeepAlive {
+            tester_present_sent: Some(super::Instant::now()),
+            ..Default::default()
+        };
+        keepalive.on_request(&uds(&[0x3e, 0x80]));
+        assert!(keepalive.on_reply(&uds(&[0x7f, 0x3e, 0x11])));
+    }
+
+    #[tokio::test]
+    async fn keepalive_tester_present_expired() {
+        let mut keepalive = super::KeepAlive {
+            tester_present_sent: Some(super::Instant::now()),
+            ..Default::default()
+        };
+        tokio::time::sleep(super::SUPPRESSED_REPLY_TIMEOUT).await;
+        assert!(keepalive.on_reply(&uds(&[0x7f, 0x3e, 0x11])));
+    }
+
+    #[tokio::test]
+    async fn keepalive_suppressed_request_pending() {
+        let mut keepalive = super::KeepAlive::default();
+        let period = std::time::Duration::from_millis(10);
+        keepalive.set_period(Some(period));
+
+        // No TesterPresent until the NRC to the scenario request
+        keepalive.on_request(&uds(&[0x3e, 0x80, 0x00]));
+        let tick = tokio::time::timeout(period * 10, keepalive.tick());
+        assert!(tick.await.is_err());
+        assert!(keepalive.on_reply(&uds(&[0x7f, 0x3e, 0x11])));
+        let tick = tokio::time::timeout(period * 10, keepalive.tick());
+        assert!(tick.await.is_ok());
+    }
 }
@@ -0,0 +1,154 @@
+//! Decoding of the ReadDTCInformation replies.
+
+use std::fmt;
+
+/// Names of the bits of a DTC status, from bit 0 to bit 7.
+const STATUS_BITS: [&str; 8] = [
+    "testFailed",
+    "testFailedThisOperationCycle",
+    "pendingDTC",
+    "confirmedDTC",
+    "testNotCompletedSinceLastClear",
+    "testFailedSinceLastClear",
+    "testNotCompletedThisOperationCycle",
+    "warningIndicatorRequested",
+];
+
+/// Diagnostic trouble code, with its status.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dtc {
+    /// 3-byte code, the last byte being the failure type
+    pub code: u32,
+    pub status: u8,
+}
+
+impl Dtc {
+    /// Decode the 4 bytes of a DTC and its status.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Dtc> {
+        match bytes {
+            [hi, mid, lo, status, ..] => Some(Dtc {
+                code: u32::from_be_bytes([0, *hi, *mid, *lo]),
+                status: *status,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Display form of the code, such as "P0123-45", the letter being the
+    /// system (Powertrain, Chassis, Body or network), and the last byte the
+    /// failure type.
+    pub fn display_code(&self) -> String {
+        let [_, hi, mid, lo] = self.code.to_be_bytes();
+        let system = ['P', 'C', 'B', 'U'][(hi >> 6) as usize];
+        format!(
+            "{system}{}{:X}{mid:02X}-{lo:02X}",
+            (hi >> 4) & 0x03,
+            hi & 0x0f
+        )
+    }
+
+    /// Names of the bits set in the status.
+    pub fn status_bits(&self) -> Vec<&'static str> {
+        STATUS_BITS
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| self.status & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl fmt::Display for Dtc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:06x}) status {:08b} [{}]",
+            self.display_code(),
+            self.code,
+            self.status,
+            self.status_bits().join(", ")
+        )
+    }
+}
+
+/// Decode a list of DTCs with their status, a trailing incomplete DTC being
+/// ignored.
+pub fn decode_dtcs(bytes: &[u8]) -> Vec<Dtc> {
+    bytes.chunks_exact(4).filter_map(Dtc::from_bytes).collect()
+}
+
+/// Print a positive response to ReadDTCInformation, starting with 0x59.
+pub fn print_reply(reply: &[u8]) {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    match reply {
+        [_, 0x01, mask, format, count_hi, count_lo, ..] => {
+            let count = u16::from_be_bytes([*count_hi, *count_lo]);
+            println!("{count} DTCs (availability mask {mask:#04x}, format {format:#04x})");
+        }
+        [_, 0x02 | 0x0a, mask, dtcs @ ..] => {
+            let dtcs = decode_dtcs(dtcs);
+            println!("{} DTCs (availability mask {mask:#04x})", dtcs.len());
+            for dtc in dtcs.iter() {
+                println!("  {dtc}");
+            }
+        }
+        [_, 0x04 | 0x06, record @ ..] => match Dtc::from_bytes(record) {
+            Some(dtc) => {
+                println!("  {dtc}");
+                println!("  Records: {}", hex(&record[4..]));
+            }
+            None => println!("No DTC record"),
+        },
+        _ => println!("Unexpected DTC reply: {}", hex(reply)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display_code() {
+        let dtc = |code| Dtc { code, status: 0 };
+        assert_eq!(dtc(0x012345).display_code(), "P0123-45");
+        assert_eq!(dtc(0x4a1b00).display_code(), "C0A1B-00");
+        assert_eq!(dtc(0x9234ff).display_code(), "B1234-FF");
+        assert_eq!(dtc(0xf00316).display_code(), "U3003-16");
+    }
+
+    #[test]
+    fn status_bits() {
+        let dtc = Dtc {
+            code: 0,
+            status: 0x89,
+        };
+        assert_eq!(
+            dtc.status_bits(),
+            vec!["testFailed", "confirmedDTC", "warningIndicatorRequested"]
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let dtcs = decode_dtcs(&[0x01, 0x23, 0x45, 0x08, 0xc1, 0x00, 0x16, 0x2f, 0xfd, 0x01]);
+        assert_eq!(
+            dtcs,
+            vec![
+                Dtc {
+                    code: 0x012345,
+                    status: 0x08
+                },
+                Dtc {
+                    code: 0xc10016,
+                    status: 0x2f
+                }
+            ]
+        );
+    }
+}
@@ -18,4 +18,52 @@ pub enum ScenarioError {
     UnexpectedUdsMessage(UdsMessage),
     #[error("Error in evaluation of \"{0}\": {1}")]
     EvalExpr(String, EvalexprError),
+    #[error("Can't open scenario file {0}: {1}")]
+    ScenarioFile(String, std::io::Error),
+    #[error("Can't parse scenario file {0}: {1}")]
+    Parse(String, serde_yaml::Error),
+    #[error("Include cycle detected: {0}")]
+    IncludeCycle(String),
+    #[error("Procedure {0} is defined several times")]
+    DuplicateProcedure(String),
+    #[error("Invalid call of procedure {0}: {1}")]
+    InvalidCall(String, String),
+    #[error("Scenario parameter {0} is required but not given")]
+    MissingParameter(String),
+    #[error("Timeout in step {0}")]
+    Timeout(String),
+    #[error("Invalid checkpoint: {0}")]
+    InvalidCheckpoint(String),
+    #[error("No reply from the ECU to service {0:#04x}")]
+    NoReply(u8),
+    #[error("Key computation failed: {0}")]
+    SeedKey(String),
+    #[error("Invalid {0} step: {1}")]
+    InvalidStep(String, String),
+}
+
+impl ScenarioError {
+    /// Name of the kind of error, such as "Nrc" or "Io".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScenarioError::NetworkConnectorDead => "NetworkConnectorDead",
+            ScenarioError::RoutingActivationFailed => "RoutingActivationFailed",
+            ScenarioError::UdsError(_) => "UdsError",
+            ScenarioError::Io(_) => "Io",
+            ScenarioError::Nrc(_) => "Nrc",
+            ScenarioError::UnexpectedUdsMessage(_) => "UnexpectedUdsMessage",
+            ScenarioError::EvalExpr(_, _) => "EvalExpr",
+            ScenarioError::ScenarioFile(_, _) => "ScenarioFile",
+            ScenarioError::Parse(_, _) => "Parse",
+            ScenarioError::IncludeCycle(_) => "IncludeCycle",
+            ScenarioError::DuplicateProcedure(_) => "DuplicateProcedure",
+            ScenarioError::InvalidCall(_, _) => "InvalidCall",
+            ScenarioError::MissingParameter(_) => "MissingParameter",
+            ScenarioError::Timeout(_) => "Timeout",
+            ScenarioError::InvalidCheckpoint(_) => "InvalidCheckpoint",
+            ScenarioError::NoReply(_) => "NoReply",
+            ScenarioError::SeedKey(_) => "SeedKey",
+            ScenarioError::InvalidStep(_, _) => "InvalidStep",
+        }
+    }
 }
@@ -4,15 +4,21 @@ use evalexpr::{
 };
 use log::{debug, info};
 use pretty_hex::pretty_hex;
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::io::{self, Read};
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use tokio::time::{self, Duration};
 use uds_rw::uds_write;
 
-use super::doip_ops::ScenarioMessage;
+use super::checkpoint::{self, Checkpoint};
+use super::doip_ops::{suppress_positive_response, ScenarioMessage};
+use super::dtc;
+use super::main::{Outcome, Settings};
 use super::parser::{self, DisconnectDoIp, Step};
+use super::seedkey;
 use super::{error::ScenarioError, parser::AbortIfNrc};
 use tokio::sync::mpsc;
 use uds_rw::{
@@ -21,18 +27,178 @@ use uds_rw::{
 };
 
 struct Context {
+    procedures: Arc<parser::Procedures>,
     last_uds_reply: UdsMessage,
     tx: mpsc::Sender<ScenarioMessage>,
     rx: mpsc::Receiver<ScenarioMessage>,
     eval_expr: EvalExprContext,
+    assertions: Assertions,
+    /// End of the whole scenario, if it has a timeout
+    deadline: Option<time::Instant>,
+    checkpoint_file: Option<PathBuf>,
+    /// Hash of the scenario, stored in its checkpoints
+    scenario_hash: u64,
+    /// Top-level step about to be executed, taken by the step itself
+    top_step: Option<TopStep>,
+    timings: Timings,
+    /// Current diagnostic session id
+    session: u8,
+    key_library: Option<Arc<seedkey::KeyLibrary>>,
+    keep_alive: KeepAliveMode,
+    /// Period of the automatic keep-alive of the non-default sessions
+    auto_keep_alive: Option<Duration>,
+    /// Period of the keep-alive currently sent by the network task
+    keep_alive_period: Option<Duration>,
+    /// ECU state to restore when the scenario ends, in registration order
+    restore: Vec<Restore>,
+    /// Position of the step being executed, such as "step 3", "procedure
+    /// unlock", "step 1"
+    location: Vec<String>,
+}
+
+/// Maximum number of nested procedure calls, against endless recursions
+const MAX_CALL_DEPTH: usize = 64;
+
+/// ECU state changed by the scenario, restored when the scenario ends.
+#[derive(Debug, Clone, PartialEq)]
+enum Restore {
+    /// Identifier under tester control, with the control enable mask of the
+    /// signals controlled, empty if the whole identifier is
+    IoControl(u16, Vec<u8>),
+    /// Communication disabled on a subnet, with the communication type bits
+    /// still disabled
+    CommunicationControl(u8, u8),
+    /// DTC setting off
+    ControlDTCSetting,
+}
+
+impl Restore {
+    /// Request restoring the ECU state.
+    fn request(&self) -> Vec<u8> {
+        match self {
+            Restore::IoControl(did, mask) => {
+                let [did_hi, did_lo] = did.to_be_bytes();
+                let control = parser::IoControlType::ReturnControlToEcu.id();
+                [&[0x2f, did_hi, did_lo, control], &mask[..]].concat()
+            }
+            Restore::CommunicationControl(subnet, disabled) => {
+                let control = parser::CommunicationControlType::EnableRxAndTx.id();
+                vec![0x28, control, (subnet << 4) | disabled]
+            }
+            Restore::ControlDTCSetting => vec![0x85, parser::DTCSetting::On.id()],
+        }
+    }
+}
+
+/// TesterPresent keep-alive requested by the scenario.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeepAliveMode {
+    /// Sent in the non-default sessions, if --tester-present is given
+    Auto,
+    Started(Duration),
+    Stopped,
+}
+
+/// Response timings of the ECU, P2server_max and P2*server_max.
+#[derive(Debug, Clone, Copy)]
+struct Timings {
+    p2: Duration,
+    /// Timeout after a responsePending NRC
+    p2_star: Duration,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            p2: Duration::from_millis(50),
+            p2_star: Duration::from_millis(5000),
+        }
+    }
+}
+
+/// Margin added to the ECU response timings for the DoIP network latency
+const NETWORK_MARGIN: Duration = Duration::from_millis(500);
+
+/// Top-level step, after which a checkpoint is written.
+#[derive(Debug, Clone, Copy)]
+struct TopStep {
+    index: usize,
+    /// Iterations already completed, if the step is a loop being resumed
+    completed_iterations: usize,
+}
+
+#[derive(Default)]
+struct Assertions {
+    passed: usize,
+    failed: Vec<String>,
+}
+
+impl Assertions {
+    fn print_summary(&self) {
+        if self.passed + self.failed.len() == 0 {
+            return;
+        }
+        println!(
+            "Assertions: {} passed, {} failed",
+            self.passed,
+            self.failed.len()
+        );
+        for failure in self.failed.iter() {
+            println!("  Failed: {failure}");
+        }
+    }
+}
+
+/// How the execution goes on after a step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    /// Go on with the next step
+    Next,
+    /// Leave the innermost loop
+    Break,
+    /// Go on with the next iteration of the innermost loop
+    Continue,
+    /// Abort the scenario
+    Abort,
 }
 
 fn execute_step<'b: 'a, 'a>(
     ctxt: &'a mut Context,
     step: &'b Step,
-) -> Pin<Box<dyn Future<Output = Result<bool, ScenarioError>> + 'a>> {
+) -> Pin<Box<dyn Future<Output = Result<Flow, ScenarioError>> + 'a>> {
     Box::pin(async move {
-        let mut abort = false;
+        let step_deadline = step
+            .timeout_ms()
+            .map(|timeout_ms| time::Instant::now() + Duration::from_millis(timeout_ms as u64));
+        let deadline = match (step_deadline, ctxt.deadline) {
+            (Some(step_deadline), Some(deadline)) => Some(step_deadline.min(deadline)),
+            (step_deadline, deadline) => step_deadline.or(deadline),
+        };
+        match deadline {
+            Some(deadline) => {
+                let depth = ctxt.location.len();
+                let res = time::timeout_at(deadline, run_step(ctxt, step)).await;
+                // The nested steps interrupted by the timeout left their position
+                ctxt.location.truncate(depth);
+                res.map_err(|_| step_timeout(ctxt, step.name()))?
+            }
+            None => run_step(ctxt, step).await,
+        }
+    })
+}
+
+/// Timeout of a step, named along with its position in the scenario.
+fn step_timeout(ctxt: &Context, name: &str) -> ScenarioError {
+    ScenarioError::Timeout(format!("{name} at {}", ctxt.location.join(", ")))
+}
+
+fn run_step<'b: 'a, 'a>(
+    ctxt: &'a mut Context,
+    step: &'b Step,
+) -> Pin<Box<dyn Future<Output = Result<Flow, ScenarioError>> + 'a>> {
+    Box::pin(async move {
+        let mut flow = Flow::Next;
+        let top_step = ctxt.top_step.take();
         use parser::Step::*;
 
         debug!("Executing step: {step:?}");
@@ -40,53 +206,283 @@ fn execute_step<'b: 'a, 'a>(
             AbortIfNrc(anrc) => {
                 if abort_if_nrc(ctxt, anrc) {
                     println!("Abort if NRC condition met, aborting scenario.");
-                    abort = true;
+                    flow = Flow::Abort;
                 }
             }
+            Break(brk) => flow = loop_control(ctxt, &brk.condition, Flow::Break)?,
+            Call(call) => {
+                flow = call_procedure(ctxt, call).await?;
+                if flow == Flow::Abort {
+                    println!("Procedure {} aborted scenario.", call.procedure);
+                }
+            }
+            ClearDTC(cdtc) => clear_dtc(ctxt, cdtc).await?,
+            CommunicationControl(cc) => communication_control(ctxt, cc).await?,
+            Continue(cont) => flow = loop_control(ctxt, &cont.condition, Flow::Continue)?,
+            ControlDTCSetting(cdtcs) => control_dtc_setting(ctxt, cdtcs).await?,
+            DiagnosticSession(ds) => diagnostic_session(ctxt, ds).await?,
             DisconnectDoIp(disc) => disconnect_doip(ctxt, disc).await?,
+            EcuReset(er) => ecu_reset(ctxt, er).await?,
             EvalExpr(expr) => eval_expr(ctxt, expr)?,
+            ExpectReply(er) => {
+                if assert_reply(ctxt, er)? {
+                    println!("Reply assertion failed, aborting scenario.");
+                    flow = Flow::Abort;
+                }
+            }
+            ForEach(fe) => {
+                flow = for_each(ctxt, fe, top_step).await?;
+                if flow == Flow::Abort {
+                    println!("ForEach loop aborted scenario.");
+                }
+            }
+            If(cond) => {
+                flow = if_else(ctxt, cond).await?;
+                if flow == Flow::Abort {
+                    println!("If branch aborted scenario.");
+                }
+            }
+            Include(inc) => {
+                flow = include(ctxt, inc).await?;
+                if flow == Flow::Abort {
+                    println!("Included scenario {} aborted scenario.", inc.filename);
+                }
+            }
+            IoControl(ioc) => io_control(ctxt, ioc).await?,
             PrintLastReply => print_last_reply(ctxt),
             RawUds(ruds) => uds_raw(ctxt, ruds).await?,
             ReadDID(did) => read_did(ctxt, did).await?,
+            ReadDTC(rdtc) => read_dtc(ctxt, rdtc).await?,
+            ReadMemoryByAddress(rmba) => read_memory_by_address(ctxt, rmba).await?,
             ReadSupportedDTC(dtc) => read_supported_dtc(ctxt, dtc).await?,
-            SleepMs(time_ms) => sleep_ms(ctxt, *time_ms).await?,
+            Repeat(rp) => {
+                flow = repeat(ctxt, rp, top_step).await?;
+                if flow == Flow::Abort {
+                    println!("Repeat loop aborted scenario.");
+                }
+            }
+            Retry(rt) => {
+                flow = retry(ctxt, rt).await?;
+                if flow == Flow::Abort {
+                    println!("Retry block aborted scenario.");
+                }
+            }
+            RoutineControl(rc) => routine_control(ctxt, rc).await?,
+            SecurityAccess(sa) => security_access(ctxt, sa).await?,
+            SleepMs(time_ms) => {
+                let time_ms = ctxt.eval_expr.evaluate(time_ms)?;
+                sleep_ms(ctxt, time_ms).await?
+            }
+            TesterPresent(tp) => tester_present(ctxt, tp).await?,
+            Try(tr) => {
+                flow = try_steps(ctxt, tr).await?;
+                if flow == Flow::Abort {
+                    println!("Try block aborted scenario.");
+                }
+            }
             WhileLoop(wl) => {
-                if while_loop(ctxt, wl).await? {
+                flow = while_loop(ctxt, wl, top_step).await?;
+                if flow == Flow::Abort {
                     println!("While loop aborted scenario.");
-                    abort = true;
                 }
             }
             WriteDID(did) => write_did(ctxt, did).await?,
+            WriteMemoryByAddress(wmba) => write_memory_by_address(ctxt, wmba).await?,
             TransferDownload(td) => transfer_download(ctxt, td).await?,
+            TransferUpload(tu) => transfer_upload(ctxt, tu).await?,
         };
-        Ok(abort)
+        Ok(flow)
     })
 }
 
-async fn execute_steps(ctxt: &mut Context, steps: &Vec<Step>) -> Result<bool, ScenarioError> {
-    let mut abort = false;
-    for step in steps {
-        abort = execute_step(ctxt, step).await?;
-        if abort {
-            break;
+async fn execute_steps(ctxt: &mut Context, steps: &Vec<Step>) -> Result<Flow, ScenarioError> {
+    let depth = ctxt.location.len();
+    for (index, step) in steps.iter().enumerate() {
+        ctxt.location.push(format!("step {}", index + 1));
+        let res = execute_step(ctxt, step).await;
+        ctxt.location.truncate(depth);
+        let flow = res?;
+        if flow != Flow::Next {
+            return Ok(flow);
         }
     }
-    Ok(abort)
+    Ok(Flow::Next)
+}
+
+/// Execute the top-level steps, starting from a checkpoint if any, and write a
+/// checkpoint after each of them.
+async fn execute_top_level(
+    ctxt: &mut Context,
+    steps: &[Step],
+    resume: Option<Checkpoint>,
+) -> Result<Flow, ScenarioError> {
+    let (first, mut completed_iterations) = match resume {
+        Some(checkpoint) => {
+            if checkpoint.scenario_hash != ctxt.scenario_hash {
+                return Err(ScenarioError::InvalidCheckpoint(
+                    "the checkpoint was written by another scenario".to_string(),
+                ));
+            }
+            if checkpoint.step_index > steps.len() {
+                return Err(ScenarioError::InvalidCheckpoint(format!(
+                    "step {} is beyond the {} steps of the scenario",
+                    checkpoint.step_index,
+                    steps.len()
+                )));
+            }
+            println!("Resuming scenario at step {}", checkpoint.step_index);
+            ctxt.eval_expr.set_variables(
+                checkpoint
+                    .variables
+                    .into_iter()
+                    .map(|(name, variable)| (name, variable.into()))
+                    .collect(),
+            )?;
+            (checkpoint.step_index, checkpoint.loop_counter.unwrap_or(0))
+        }
+        None => (0, 0),
+    };
+    for (index, step) in steps.iter().enumerate().skip(first) {
+        ctxt.top_step = Some(TopStep {
+            index,
+            completed_iterations,
+        });
+        completed_iterations = 0;
+        ctxt.location = vec![format!("step {}", index + 1)];
+        let flow = execute_step(ctxt, step).await?;
+        if flow == Flow::Abort {
+            return Ok(flow);
+        }
+        write_checkpoint(ctxt, index + 1, None)?;
+        if flow != Flow::Next {
+            return Ok(flow);
+        }
+    }
+    Ok(Flow::Next)
+}
+
+fn write_checkpoint(
+    ctxt: &Context,
+    step_index: usize,
+    loop_counter: Option<usize>,
+) -> Result<(), ScenarioError> {
+    let Some(checkpoint_file) = &ctxt.checkpoint_file else {
+        return Ok(());
+    };
+    let checkpoint = Checkpoint {
+        scenario_hash: ctxt.scenario_hash,
+        step_index,
+        loop_counter,
+        // The ECU state belongs to the interrupted run, it isn't restored
+        variables: ctxt
+            .eval_expr
+            .save_variables()
+            .iter()
+            .filter(|(name, _)| !EvalExprContext::ECU_STATE_VARIABLES.contains(&name.as_str()))
+            .map(|(name, value)| (name.clone(), value.into()))
+            .collect(),
+    };
+    checkpoint.write(checkpoint_file)
+}
+
+/// Write the checkpoint of an iteration of a top-level loop.
+fn write_loop_checkpoint(
+    ctxt: &Context,
+    top_step: Option<TopStep>,
+    completed_iterations: usize,
+) -> Result<(), ScenarioError> {
+    match top_step {
+        Some(top_step) => write_checkpoint(ctxt, top_step.index, Some(completed_iterations)),
+        None => Ok(()),
+    }
 }
 
 pub async fn execute(
-    steps: parser::Steps,
+    scenario: parser::Scenario,
+    parameters: BTreeMap<String, parser::ParamValue>,
+    settings: Settings,
     tx: mpsc::Sender<ScenarioMessage>,
     rx: mpsc::Receiver<ScenarioMessage>,
-) -> Result<(), ScenarioError> {
+) -> Result<Outcome, ScenarioError> {
+    let scenario_hash = checkpoint::scenario_hash(&scenario)?;
     let mut ctxt: Context = Context {
+        procedures: Arc::new(scenario.procedures),
         last_uds_reply: UdsMessage::RawUds(message::RawUds { data: vec![] }),
         rx,
         tx,
         eval_expr: EvalExprContext::new(),
+        assertions: Assertions::default(),
+        deadline: settings
+            .timeout
+            .map(|timeout| time::Instant::now() + timeout),
+        checkpoint_file: settings.checkpoint_file,
+        scenario_hash,
+        top_step: None,
+        timings: Timings::default(),
+        session: 0x01,
+        key_library: None,
+        keep_alive: KeepAliveMode::Auto,
+        auto_keep_alive: settings.tester_present,
+        keep_alive_period: None,
+        restore: vec![],
+        location: vec![],
     };
 
-    execute_steps(&mut ctxt, &steps).await.map(|_| ())
+    if let Some(filename) = settings.seedkey_library {
+        let key_library = Arc::new(seedkey::KeyLibrary::load(&filename)?);
+        ctxt.eval_expr.set_key_library(key_library.clone())?;
+        ctxt.key_library = Some(key_library);
+    }
+
+    ctxt.eval_expr
+        .set_variable("session", Value::Int(ctxt.session as i64))?;
+
+    ctxt.eval_expr.set_variables(
+        parameters
+            .into_iter()
+            .map(|(name, value)| (name, param_value(value)))
+            .collect(),
+    )?;
+
+    let res = execute_top_level(&mut ctxt, &scenario.steps, settings.resume).await;
+    restore_ecu_state(&mut ctxt).await;
+    ctxt.assertions.print_summary();
+    let flow = res?;
+    if flow == Flow::Abort {
+        Ok(Outcome::Aborted)
+    } else if !ctxt.assertions.failed.is_empty() {
+        Ok(Outcome::AssertionsFailed(ctxt.assertions.failed.len()))
+    } else {
+        Ok(Outcome::Success)
+    }
+}
+
+/// Restore the ECU state changed by the scenario, even if it failed, the last
+/// change being restored first.
+async fn restore_ecu_state(ctxt: &mut Context) {
+    while let Some(restore) = ctxt.restore.pop() {
+        let data = restore.request();
+        let sid = data[0];
+        println!("Restoring {restore:?}");
+        let uds = UdsMessage::RawUds(message::RawUds { data });
+        let res = match request_response(ctxt, uds).await {
+            Ok(()) => expect_reply(ctxt, sid),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            println!("Restoring {restore:?} failed: {err}");
+        }
+    }
+}
+
+fn param_value(value: parser::ParamValue) -> Value {
+    match value {
+        parser::ParamValue::Int(i) => Value::Int(i),
+        parser::ParamValue::Bytes(bytes) => {
+            Value::Tuple(bytes.iter().map(|b| Value::Int(*b as i64)).collect())
+        }
+        parser::ParamValue::String(s) => Value::String(s),
+    }
 }
 
 async fn sleep_ms(ctxt: &mut Context, sleep_ms: usize) -> Result<(), ScenarioError> {
@@ -125,6 +521,60 @@ async fn disconnect_doip(ctxt: &mut Context, disc: &DisconnectDoIp) -> Result<()
     Ok(())
 }
 
+async fn ecu_reset(ctxt: &mut Context, er: &parser::EcuReset) -> Result<(), ScenarioError> {
+    let mut reset_type = er.reset_type.id();
+    if er.suppress_response {
+        reset_type |= 0x80;
+    }
+    let uds = UdsMessage::RawUds(message::RawUds {
+        data: vec![0x11, reset_type],
+    });
+    match request_response(ctxt, uds).await {
+        Ok(()) => expect_reply_unless_suppressed(ctxt, 0x11, er.suppress_response)?,
+        Err(ScenarioError::NoReply(_)) => {
+            println!("No reply to ECUReset, ECU already resetting.")
+        }
+        Err(err) => return Err(err),
+    }
+
+    // Not to reconnect to the ECU before it went down
+    if let Some(reset_delay_ms) = er.reset_delay_ms {
+        time::sleep(Duration::from_millis(reset_delay_ms as u64)).await;
+    }
+    let max_wait = Duration::from_millis(er.max_wait_ms.unwrap_or(30000) as u64);
+    let reconnect = ScenarioMessage::ReconnectReq {
+        max_wait,
+        wait_announcement: er.wait_announcement,
+    };
+    ctxt.tx
+        .send(reconnect)
+        .await
+        .map_err(|_| ScenarioError::NetworkConnectorDead)?;
+    loop {
+        match ctxt.rx.recv().await {
+            Some(ScenarioMessage::NotifyDoIpCnxRoutingAck) => break,
+            Some(ScenarioMessage::NotifyReconnectFailed(ScenarioError::Io(err)))
+                if err.kind() == io::ErrorKind::TimedOut =>
+            {
+                return Err(step_timeout(ctxt, "EcuReset"))
+            }
+            Some(ScenarioMessage::NotifyReconnectFailed(err)) => return Err(err),
+            Some(ScenarioMessage::Uds(rsp)) => debug!("Dropping UDS reply before reset: {rsp}"),
+            Some(_) => {}
+            None => return Err(ScenarioError::NetworkConnectorDead),
+        }
+    }
+
+    // The ECU is back in the default session, with its default timings and
+    // without the state changed by the scenario
+    ctxt.session = 0x01;
+    ctxt.timings = Timings::default();
+    ctxt.restore.clear();
+    ctxt.eval_expr
+        .set_variable("session", Value::Int(ctxt.session as i64))?;
+    update_keep_alive(ctxt).await
+}
+
 fn abort_if_nrc(ctxt: &Context, anrc: &AbortIfNrc) -> bool {
     if let UdsMessage::Nrc(unrc) = &ctxt.last_uds_reply {
         let nrc = unrc.nrc;
@@ -137,6 +587,67 @@ fn abort_if_nrc(ctxt: &Context, anrc: &AbortIfNrc) -> bool {
     }
 }
 
+/// Check the last reply against an ExpectReply, and return true if the scenario
+/// must be aborted.
+fn assert_reply(ctxt: &mut Context, er: &parser::ExpectReply) -> Result<bool, ScenarioError> {
+    let reply = ctxt.eval_expr.reply_bytes();
+    let failure = check_reply(ctxt, er, &reply)?;
+    match failure {
+        None => {
+            ctxt.assertions.passed += 1;
+            Ok(false)
+        }
+        Some(failure) => {
+            let failure = format!("reply {}: {failure}", hex_string(&reply, " "));
+            println!("ExpectReply failed: {failure}");
+            ctxt.assertions.failed.push(failure);
+            Ok(!matches!(er.on_failure, Some(parser::OnFailure::Continue)))
+        }
+    }
+}
+
+fn check_reply(
+    ctxt: &Context,
+    er: &parser::ExpectReply,
+    reply: &[u8],
+) -> Result<Option<String>, ScenarioError> {
+    if let Some(starts_with) = &er.starts_with {
+        let prefix = starts_with.get_bytes(|varname| ctxt.eval_expr.get_tuple_variable(varname))?;
+        if !reply.starts_with(&prefix) {
+            return Ok(Some(format!(
+                "doesn't start with {}",
+                hex_string(&prefix, " ")
+            )));
+        }
+    }
+    for expected in er.bytes.iter().flatten() {
+        let mask = expected.mask.unwrap_or(0xff);
+        match reply.get(expected.index) {
+            Some(b) if b & mask == expected.value & mask => {}
+            _ => {
+                return Ok(Some(format!(
+                    "byte {} isn't {:02x} under mask {:02x}",
+                    expected.index, expected.value, mask
+                )))
+            }
+        }
+    }
+    if let Some(regex) = &er.regex {
+        if !regex.compiled.is_match(&hex_string(reply, "")) {
+            return Ok(Some(format!("doesn't match {}", regex.str)));
+        }
+    }
+    Ok(None)
+}
+
+fn hex_string(bytes: &[u8], separator: &str) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
 fn print_last_reply(ctxt: &Context) {
     let uds = &ctxt.last_uds_reply;
     println!("Rx UDS: {uds}");
@@ -155,18 +666,31 @@ fn expect_reply(ctxt: &Context, request_sid: u8) -> Result<(), ScenarioError> {
     Ok(())
 }
 
+/// Check the reply to a request, which is missing if the positive response
+/// was suppressed and the request succeeded.
+fn expect_reply_unless_suppressed(
+    ctxt: &Context,
+    request_sid: u8,
+    suppressed: bool,
+) -> Result<(), ScenarioError> {
+    match &ctxt.last_uds_reply {
+        UdsMessage::RawUds(raw) if suppressed && raw.data.is_empty() => Ok(()),
+        _ => expect_reply(ctxt, request_sid),
+    }
+}
+
 async fn transfer_download(
     ctxt: &mut Context,
     td: &parser::TransferDownload,
 ) -> Result<(), ScenarioError> {
-    let mut file = std::fs::File::open(&td.filename)?;
+    let mut file = std::fs::File::open(ctxt.eval_expr.evaluate(&td.filename)?)?;
     let req = message::RequestDownloadReq {
         compression_method: td.compression_method,
         encryption_method: td.encrypt_method,
         memory_size_bytes: 4,
         memory_address_bytes: 4,
-        memory_address: td.addr,
-        memory_size: td.memorysize,
+        memory_address: ctxt.eval_expr.evaluate(&td.addr)?,
+        memory_size: ctxt.eval_expr.evaluate(&td.memorysize)?,
     };
     let uds_req = UdsMessage::RequestDownloadReq(req);
     let req_sid: u8 = (&uds_req).into();
@@ -179,21 +703,21 @@ async fn transfer_download(
         panic!("Impossible case, please contact the developper");
     };
 
-    let mut req0 = message::TransferDataReq {
-        block_sequence_counter: 1,
-        // max_block_size is : SID (1 byte) + block_seq_counter (1 byte)
-        data: vec![0u8; max_block_size - 1 - 1],
-    };
+    let mut counter = BlockSequenceCounter::default();
+    // max_block_size is : SID (1 byte) + block_seq_counter (1 byte)
+    let block_size = max_block_size - 1 - 1;
     loop {
-        let mut req = req0.clone();
-        req0.block_sequence_counter = req0.block_sequence_counter.checked_add(1).unwrap_or(0);
+        let mut req = message::TransferDataReq {
+            block_sequence_counter: counter.next(),
+            data: vec![0u8; block_size],
+        };
         let nb = file.read(&mut req.data)?;
         req.data.resize(nb, 0);
         let uds_req = UdsMessage::TransferDataReq(req);
         let req_sid: u8 = (&uds_req).into();
         request_response(ctxt, uds_req).await?;
         expect_reply(ctxt, req_sid)?;
-        if nb < req0.data.len() {
+        if nb < block_size {
             break;
         }
     }
@@ -207,15 +731,292 @@ async fn transfer_download(
     Ok(())
 }
 
+async fn transfer_upload(
+    ctxt: &mut Context,
+    tu: &parser::TransferUpload,
+) -> Result<(), ScenarioError> {
+    const STEP: &str = "TransferUpload";
+    let format = MemoryFormat::new(STEP, tu.address_bytes, Some(tu.size_bytes.unwrap_or(4)))?;
+    let addr = ctxt.eval_expr.evaluate(&tu.addr)?;
+    let memorysize = ctxt.eval_expr.evaluate(&tu.memorysize)?;
+    let filename = match &tu.filename {
+        Some(filename) => Some(ctxt.eval_expr.evaluate(filename)?),
+        None => None,
+    };
+    let data_format = (tu.compression_method << 4) | (tu.encrypt_method & 0x0f);
+    let req = message::RawUds {
+        data: [
+            &[0x35, data_format][..],
+            &format.encode(STEP, addr, memorysize)?,
+        ]
+        .concat(),
+    };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply(ctxt, 0x35)?;
+
+    // Positive response: 75 <length format> <max number of block length>
+    let reply = ctxt.eval_expr.reply_bytes();
+    let length_bytes = reply.get(1).map_or(0, |lfid| (lfid >> 4) as usize);
+    let max_block_size = reply
+        .get(2..2 + length_bytes)
+        .filter(|bytes| !bytes.is_empty())
+        .map(|bytes| {
+            bytes
+                .iter()
+                .fold(0usize, |size, b| (size << 8) | *b as usize)
+        })
+        .ok_or_else(|| ScenarioError::UnexpectedUdsMessage(ctxt.last_uds_reply.clone()))?;
+    debug!("RequestUpload max block size: {max_block_size}");
+
+    let mut data: Vec<u8> = Vec::with_capacity(memorysize);
+    let mut counter = BlockSequenceCounter::default();
+    let mut empty_block = None;
+    while data.len() < memorysize {
+        let block_sequence_counter = counter.next();
+        let req = message::RawUds {
+            data: vec![0x36, block_sequence_counter],
+        };
+        request_response(ctxt, UdsMessage::RawUds(req)).await?;
+        expect_reply(ctxt, 0x36)?;
+
+        // Positive response: 76 <block sequence counter> <data>
+        let reply = ctxt.eval_expr.reply_bytes();
+        match reply.get(1) {
+            Some(bsc) if *bsc == block_sequence_counter => {}
+            _ => {
+                return Err(ScenarioError::UnexpectedUdsMessage(
+                    ctxt.last_uds_reply.clone(),
+                ))
+            }
+        }
+        if reply.len() <= 2 {
+            empty_block = Some(ctxt.last_uds_reply.clone());
+            break;
+        }
+        data.extend_from_slice(&reply[2..]);
+    }
+    data.truncate(memorysize);
+
+    let req = message::TransferExitReq { user_data: vec![] };
+    let uds_req = UdsMessage::TransferExitReq(req);
+    let req_sid: u8 = (&uds_req).into();
+    request_response(ctxt, uds_req).await?;
+    expect_reply(ctxt, req_sid)?;
+
+    // The ECU ended the transfer before memorysize bytes were uploaded
+    if let Some(reply) = empty_block {
+        debug!(
+            "TransferUpload: {} bytes uploaded out of {memorysize}",
+            data.len()
+        );
+        return Err(ScenarioError::UnexpectedUdsMessage(reply));
+    }
+    store_memory(ctxt, &data, filename, &tu.variable)
+}
+
+/// Write the data read from the ECU memory into a file and into an evalexpr
+/// variable, as a tuple of bytes.
+fn store_memory(
+    ctxt: &mut Context,
+    data: &[u8],
+    filename: Option<String>,
+    variable: &Option<String>,
+) -> Result<(), ScenarioError> {
+    if let Some(filename) = filename {
+        std::fs::write(filename, data)?;
+    }
+    if let Some(variable) = variable {
+        let value = Value::Tuple(data.iter().map(|b| Value::Int(*b as i64)).collect());
+        ctxt.eval_expr.set_variable(variable, value)?;
+    }
+    Ok(())
+}
+
+/// Block sequence counter of the TransferData requests, starting at 1 and
+/// wrapping around from 0xff to 0x00.
+struct BlockSequenceCounter(u8);
+
+impl Default for BlockSequenceCounter {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl BlockSequenceCounter {
+    fn next(&mut self) -> u8 {
+        let counter = self.0;
+        self.0 = self.0.wrapping_add(1);
+        counter
+    }
+}
+
+/// addressAndLengthFormatIdentifier of the ReadMemoryByAddress,
+/// WriteMemoryByAddress and RequestUpload requests.
+struct MemoryFormat {
+    address_bytes: u8,
+    size_bytes: u8,
+}
+
+impl MemoryFormat {
+    fn new(
+        step: &str,
+        address_bytes: Option<u8>,
+        size_bytes: Option<u8>,
+    ) -> Result<Self, ScenarioError> {
+        let address_bytes = address_bytes.unwrap_or(4);
+        let size_bytes = size_bytes.unwrap_or(2);
+        for (field, nb) in [("address_bytes", address_bytes), ("size_bytes", size_bytes)] {
+            if !(1..=8).contains(&nb) {
+                return Err(ScenarioError::InvalidStep(
+                    step.to_string(),
+                    format!("{field} must be between 1 and 8, not {nb}"),
+                ));
+            }
+        }
+        Ok(Self {
+            address_bytes,
+            size_bytes,
+        })
+    }
+
+    /// Encode the identifier, the address and the size of a request.
+    fn encode(&self, step: &str, addr: usize, size: usize) -> Result<Vec<u8>, ScenarioError> {
+        let truncate = |field: &str, value: usize, nb: u8| {
+            let bytes = (value as u64).to_be_bytes();
+            let (high, low) = bytes.split_at(bytes.len() - nb as usize);
+            if high.iter().any(|b| *b != 0) {
+                return Err(ScenarioError::InvalidStep(
+                    step.to_string(),
+                    format!("{field} {value:#x} doesn't fit in {nb} bytes"),
+                ));
+            }
+            Ok(low.to_vec())
+        };
+        Ok([
+            vec![(self.size_bytes << 4) | self.address_bytes],
+            truncate("address", addr, self.address_bytes)?,
+            truncate("size", size, self.size_bytes)?,
+        ]
+        .concat())
+    }
+
+    /// Check that a whole memory area can be addressed, before its first
+    /// request is sent.
+    fn check_area(&self, step: &str, addr: usize, size: usize) -> Result<(), ScenarioError> {
+        let last = addr.checked_add(size.saturating_sub(1)).ok_or_else(|| {
+            ScenarioError::InvalidStep(
+                step.to_string(),
+                format!("memory area of {size:#x} bytes at {addr:#x} overflows"),
+            )
+        })?;
+        self.encode(step, last, 0).map(|_| ())
+    }
+}
+
+fn memory_chunk_size(step: &str, chunk_size: Option<usize>) -> Result<usize, ScenarioError> {
+    match chunk_size.unwrap_or(256) {
+        0 => Err(ScenarioError::InvalidStep(
+            step.to_string(),
+            "chunk_size must not be 0".to_string(),
+        )),
+        chunk_size => Ok(chunk_size),
+    }
+}
+
+/// Address of the byte at offset in a memory area starting at addr.
+async fn read_memory_by_address(
+    ctxt: &mut Context,
+    rmba: &parser::ReadMemoryByAddress,
+) -> Result<(), ScenarioError> {
+    const STEP: &str = "ReadMemoryByAddress";
+    let format = MemoryFormat::new(STEP, rmba.address_bytes, rmba.size_bytes)?;
+    let chunk_size = memory_chunk_size(STEP, rmba.chunk_size)?;
+    let addr = ctxt.eval_expr.evaluate(&rmba.addr)?;
+    let size = ctxt.eval_expr.evaluate(&rmba.size)?;
+    let filename = match &rmba.filename {
+        Some(filename) => Some(ctxt.eval_expr.evaluate(filename)?),
+        None => None,
+    };
+    format.check_area(STEP, addr, size)?;
+
+    let mut data: Vec<u8> = Vec::with_capacity(size);
+    while data.len() < size {
+        let len = chunk_size.min(size - data.len());
+        let req = message::RawUds {
+            data: [&[0x23][..], &format.encode(STEP, addr + data.len(), len)?].concat(),
+        };
+        request_response(ctxt, UdsMessage::RawUds(req)).await?;
+        expect_reply(ctxt, 0x23)?;
+
+        // Positive response: 63 <data>
+        let reply = ctxt.eval_expr.reply_bytes();
+        if reply.len() != 1 + len {
+            return Err(ScenarioError::UnexpectedUdsMessage(
+                ctxt.last_uds_reply.clone(),
+            ));
+        }
+        data.extend_from_slice(&reply[1..]);
+    }
+
+    store_memory(ctxt, &data, filename, &rmba.variable)
+}
+
+async fn write_memory_by_address(
+    ctxt: &mut Context,
+    wmba: &parser::WriteMemoryByAddress,
+) -> Result<(), ScenarioError> {
+    const STEP: &str = "WriteMemoryByAddress";
+    let format = MemoryFormat::new(STEP, wmba.address_bytes, wmba.size_bytes)?;
+    let chunk_size = memory_chunk_size(STEP, wmba.chunk_size)?;
+    let addr = ctxt.eval_expr.evaluate(&wmba.addr)?;
+    let data = wmba
+        .data
+        .get_bytes(|varname| ctxt.eval_expr.get_tuple_variable(varname))?;
+    format.check_area(STEP, addr, data.len())?;
+
+    for (i, chunk) in data.chunks(chunk_size).enumerate() {
+        let header = format.encode(STEP, addr + i * chunk_size, chunk.len())?;
+        let req = message::RawUds {
+            data: [&[0x3d][..], &header, chunk].concat(),
+        };
+        request_response(ctxt, UdsMessage::RawUds(req)).await?;
+        expect_reply(ctxt, 0x3d)?;
+
+        // Positive response: 7d <alfid> <address> <size>
+        let reply = ctxt.eval_expr.reply_bytes();
+        if reply.get(1..) != Some(&header[..]) {
+            return Err(ScenarioError::UnexpectedUdsMessage(
+                ctxt.last_uds_reply.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 async fn request_response(ctxt: &mut Context, uds: UdsMessage) -> Result<(), ScenarioError> {
     let uds = uds_rw::uds_rawuds_remove_raw(uds);
+    let sid: u8 = (&uds).into();
+    let mut req: Vec<u8> = vec![];
+    let mut suppressed = uds_write(&mut req, &uds).is_ok() && suppress_positive_response(&req);
+    drop_late_replies(ctxt).await;
     info!(target: "uds", "Tx UDS: {uds}");
     let r = ctxt.tx.send(ScenarioMessage::Uds(uds)).await;
     if r.is_err() {
         return Err(ScenarioError::NetworkConnectorDead);
     }
+    let mut deadline = time::Instant::now() + ctxt.timings.p2 + NETWORK_MARGIN;
     loop {
-        let rsp = ctxt.rx.recv().await;
+        let rsp = match time::timeout_at(deadline, ctxt.rx.recv()).await {
+            Ok(rsp) => rsp,
+            // Only an NRC is expected when the positive response is suppressed
+            Err(_) if suppressed => {
+                debug!("No reply to {sid:02x}, positive response suppressed");
+                ctxt.last_uds_reply = UdsMessage::RawUds(message::RawUds { data: vec![] });
+                ctxt.eval_expr.set_reply(&ctxt.last_uds_reply);
+                break;
+            }
+            Err(_) => return Err(ScenarioError::NoReply(sid)),
+        };
         if rsp.is_none() {
             return Err(ScenarioError::NetworkConnectorDead);
         }
@@ -226,11 +1027,18 @@ async fn request_response(ctxt: &mut Context, uds: UdsMessage) -> Result<(), Sce
             }
             ScenarioMessage::Uds(rsp) => {
                 info!(target: "uds", "Rx UDS: {rsp}");
+                if !is_reply_to(sid, &rsp) {
+                    debug!("Dropping UDS reply not answering {sid:02x}: {rsp}");
+                    continue;
+                }
                 ctxt.eval_expr.set_reply(&rsp);
                 ctxt.last_uds_reply = rsp;
                 let reply = &ctxt.last_uds_reply;
                 if let UdsMessage::Nrc(rnrc) = reply {
                     if rnrc.nrc == 0x78 {
+                        // The final response is sent even if it was suppressed
+                        suppressed = false;
+                        deadline = time::Instant::now() + ctxt.timings.p2_star + NETWORK_MARGIN;
                         continue;
                     }
                 }
@@ -245,6 +1053,34 @@ async fn request_response(ctxt: &mut Context, uds: UdsMessage) -> Result<(), Sce
     Ok(())
 }
 
+/// Whether a reply answers a request of service `sid`, either positively or
+/// with an NRC rejecting this service.
+fn is_reply_to(sid: u8, reply: &UdsMessage) -> bool {
+    let mut data: Vec<u8> = vec![];
+    if uds_write(&mut data, reply).is_err() {
+        return false;
+    }
+    match data[..] {
+        [0x7f, rejected_sid, ..] => rejected_sid == sid,
+        [reply_sid, ..] => reply_sid == sid | 0x40,
+        [] => false,
+    }
+}
+
+/// Drop the replies received after their request timed out, so that they are
+/// not mistaken for the reply of the next request.
+async fn drop_late_replies(ctxt: &mut Context) {
+    while let Ok(rsp) = ctxt.rx.try_recv() {
+        match rsp {
+            ScenarioMessage::AliveCheckReq => {
+                let _ = ctxt.tx.send(ScenarioMessage::AliveCheckRsp).await;
+            }
+            ScenarioMessage::Uds(rsp) => debug!("Dropping late UDS reply: {rsp}"),
+            _ => {}
+        }
+    }
+}
+
 async fn uds_raw(ctxt: &mut Context, ruds: &parser::RawUds) -> Result<(), ScenarioError> {
     let req = message::RawUds {
         data: ruds
@@ -255,12 +1091,450 @@ async fn uds_raw(ctxt: &mut Context, ruds: &parser::RawUds) -> Result<(), Scenar
     request_response(ctxt, uds).await
 }
 
+async fn diagnostic_session(
+    ctxt: &mut Context,
+    ds: &parser::DiagnosticSession,
+) -> Result<(), ScenarioError> {
+    let session = ds.session.id();
+    let mut subfunction = session;
+    if ds.suppress_response {
+        subfunction |= 0x80;
+    }
+    let req = message::RawUds {
+        data: vec![0x10, subfunction],
+    };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply_unless_suppressed(ctxt, 0x10, ds.suppress_response)?;
+
+    // Positive response, unless suppressed: 50 <session> <P2 in ms> <P2* in
+    // 10ms>, the timings being kept when missing
+    let reply = ctxt.eval_expr.reply_bytes();
+    if !reply.is_empty() && reply.get(1) != Some(&session) {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    if let [_, _, p2_hi, p2_lo, p2_star_hi, p2_star_lo, ..] = reply[..] {
+        ctxt.timings = Timings {
+            p2: Duration::from_millis(u16::from_be_bytes([p2_hi, p2_lo]) as u64),
+            p2_star: Duration::from_millis(
+                u16::from_be_bytes([p2_star_hi, p2_star_lo]) as u64 * 10,
+            ),
+        };
+        debug!("ECU timings: {:?}", ctxt.timings);
+    }
+    ctxt.session = session;
+    ctxt.eval_expr
+        .set_variable("session", Value::Int(session as i64))?;
+    update_keep_alive(ctxt).await
+}
+
+async fn tester_present(
+    ctxt: &mut Context,
+    tp: &parser::TesterPresent,
+) -> Result<(), ScenarioError> {
+    ctxt.keep_alive = if tp.stop {
+        KeepAliveMode::Stopped
+    } else {
+        KeepAliveMode::Started(Duration::from_millis(tp.period_ms.unwrap_or(2000) as u64))
+    };
+    update_keep_alive(ctxt).await
+}
+
+/// Have the network task send TesterPresent as requested by the scenario,
+/// the automatic keep-alive only running in the non-default sessions.
+async fn update_keep_alive(ctxt: &mut Context) -> Result<(), ScenarioError> {
+    let period = match ctxt.keep_alive {
+        KeepAliveMode::Auto => ctxt.auto_keep_alive.filter(|_| ctxt.session != 0x01),
+        KeepAliveMode::Started(period) => Some(period),
+        KeepAliveMode::Stopped => None,
+    };
+    if period != ctxt.keep_alive_period {
+        ctxt.tx
+            .send(ScenarioMessage::KeepAlive(period))
+            .await
+            .map_err(|_| ScenarioError::NetworkConnectorDead)?;
+        ctxt.keep_alive_period = period;
+    }
+    Ok(())
+}
+
+async fn io_control(ctxt: &mut Context, ioc: &parser::IoControl) -> Result<(), ScenarioError> {
+    let did = ctxt.eval_expr.evaluate(&ioc.did)?;
+    let get_bytes = |raw: &Option<parser::RawBytes>| match raw {
+        Some(raw) => raw.get_bytes(|varname| ctxt.eval_expr.get_tuple_variable(varname)),
+        None => Ok(vec![]),
+    };
+    let state = get_bytes(&ioc.state)?;
+    let mask = get_bytes(&ioc.mask)?;
+    let [did_hi, did_lo] = did.to_be_bytes();
+    let control = ioc.control.id();
+    let req = message::RawUds {
+        data: [&[0x2f, did_hi, did_lo, control], &state[..], &mask[..]].concat(),
+    };
+
+    // Registered before sending, as the ECU may take the control without its
+    // reply being received, returning the control to the ECU being harmless.
+    // The signals are only released once the ECU got their control back.
+    let controlled = take_io_control(ctxt, did);
+    let returned = ioc.control == parser::IoControlType::ReturnControlToEcu;
+    let taken = if returned {
+        controlled.clone()
+    } else {
+        io_control_mask(controlled.clone(), &mask, false)
+    };
+    if let Some(taken) = taken {
+        ctxt.restore.push(Restore::IoControl(did, taken));
+    }
+
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply(ctxt, 0x2f)?;
+
+    // Positive response: 6f <did> <control> <control status record>
+    let reply = ctxt.eval_expr.reply_bytes();
+    if reply.get(1..4) != Some(&[did_hi, did_lo, control][..]) {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    if returned {
+        take_io_control(ctxt, did);
+        if let Some(controlled) = io_control_mask(controlled, &mask, true) {
+            ctxt.restore.push(Restore::IoControl(did, controlled));
+        }
+    }
+    Ok(())
+}
+
+/// Remove the restore entry of an identifier under tester control, returning
+/// the control enable mask of its signals.
+fn take_io_control(ctxt: &mut Context, did: u16) -> Option<Vec<u8>> {
+    ctxt.restore
+        .iter()
+        .position(|r| matches!(r, Restore::IoControl(d, _) if *d == did))
+        .and_then(|pos| match ctxt.restore.remove(pos) {
+            Restore::IoControl(_, controlled) => Some(controlled),
+            _ => None,
+        })
+}
+
+/// Control enable mask of the signals of an identifier still under tester
+/// control after a request with mask, an empty mask selecting all the signals.
+fn io_control_mask(controlled: Option<Vec<u8>>, mask: &[u8], returned: bool) -> Option<Vec<u8>> {
+    let Some(controlled) = controlled else {
+        return (!returned).then(|| mask.to_vec());
+    };
+    if controlled.is_empty() || mask.is_empty() {
+        // Only the whole identifier can be returned to the ECU
+        return (!returned || !mask.is_empty()).then(Vec::new);
+    }
+    let byte = |bytes: &[u8], i: usize| bytes.get(i).copied().unwrap_or(0);
+    let controlled: Vec<u8> = (0..controlled.len().max(mask.len()))
+        .map(|i| {
+            if returned {
+                byte(&controlled, i) & !byte(mask, i)
+            } else {
+                byte(&controlled, i) | byte(mask, i)
+            }
+        })
+        .collect();
+    controlled.iter().any(|&b| b != 0).then_some(controlled)
+}
+
+async fn communication_control(
+    ctxt: &mut Context,
+    cc: &parser::CommunicationControl,
+) -> Result<(), ScenarioError> {
+    let subnet = cc.subnet.unwrap_or(0x0);
+    if subnet > 0x0f {
+        return Err(ScenarioError::InvalidStep(
+            "CommunicationControl".to_string(),
+            format!("subnet {subnet:#x} isn't between 0x0 and 0xf"),
+        ));
+    }
+    let communication_type = cc
+        .communication
+        .unwrap_or(parser::CommunicationType::Normal);
+    let communication = (subnet << 4) | communication_type.id();
+    let mut control = cc.control.id();
+    if cc.suppress_response {
+        control |= 0x80;
+    }
+    let req = message::RawUds {
+        data: vec![0x28, control, communication],
+    };
+
+    // Registered before sending, as the ECU may disable the communication
+    // without its reply being received, enabling it again being harmless. The
+    // communication is only forgotten once the ECU enabled it.
+    let disabled = take_communication_control(ctxt, subnet);
+    let enabled = cc.control == parser::CommunicationControlType::EnableRxAndTx;
+    let disabling = if enabled {
+        disabled
+    } else {
+        disabled | communication_type.id()
+    };
+    if disabling != 0 {
+        ctxt.restore
+            .push(Restore::CommunicationControl(subnet, disabling));
+    }
+
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply_unless_suppressed(ctxt, 0x28, cc.suppress_response)?;
+
+    // Positive response: 68 <control>
+    let reply = ctxt.eval_expr.reply_bytes();
+    if !reply.is_empty() && reply.get(1) != Some(&cc.control.id()) {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    if enabled {
+        take_communication_control(ctxt, subnet);
+        let disabled = disabled & !communication_type.id();
+        if disabled != 0 {
+            ctxt.restore
+                .push(Restore::CommunicationControl(subnet, disabled));
+        }
+    }
+    Ok(())
+}
+
+/// Remove the restore entry of a subnet whose communication is disabled,
+/// returning the disabled communication types.
+fn take_communication_control(ctxt: &mut Context, subnet: u8) -> u8 {
+    ctxt.restore
+        .iter()
+        .position(|r| matches!(r, Restore::CommunicationControl(s, _) if *s == subnet))
+        .and_then(|pos| match ctxt.restore.remove(pos) {
+            Restore::CommunicationControl(_, disabled) => Some(disabled),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+async fn control_dtc_setting(
+    ctxt: &mut Context,
+    cdtcs: &parser::ControlDTCSetting,
+) -> Result<(), ScenarioError> {
+    let mut setting = cdtcs.setting.id();
+    if cdtcs.suppress_response {
+        setting |= 0x80;
+    }
+    let req = message::RawUds {
+        data: vec![0x85, setting],
+    };
+
+    // Registered before sending, as for the CommunicationControl
+    let restore = Restore::ControlDTCSetting;
+    let off = cdtcs.setting == parser::DTCSetting::Off;
+    if off {
+        ctxt.restore.retain(|r| *r != restore);
+        ctxt.restore.push(restore.clone());
+    }
+
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply_unless_suppressed(ctxt, 0x85, cdtcs.suppress_response)?;
+
+    // Positive response: c5 <setting>
+    let reply = ctxt.eval_expr.reply_bytes();
+    if !reply.is_empty() && reply.get(1) != Some(&cdtcs.setting.id()) {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    if !off {
+        ctxt.restore.retain(|r| *r != restore);
+    }
+    Ok(())
+}
+
+async fn routine_control(
+    ctxt: &mut Context,
+    rc: &parser::RoutineControl,
+) -> Result<(), ScenarioError> {
+    let routine = ctxt.eval_expr.evaluate(&rc.routine)?;
+    let option = match &rc.option {
+        Some(option) => option.get_bytes(|varname| ctxt.eval_expr.get_tuple_variable(varname))?,
+        None => vec![],
+    };
+    routine_request(
+        ctxt,
+        rc.control.id(),
+        routine,
+        &option,
+        rc.suppress_response,
+    )
+    .await?;
+
+    let Some(poll_until) = &rc.poll_until else {
+        return Ok(());
+    };
+    let interval = rc.poll_interval_ms.unwrap_or(100);
+    let deadline =
+        time::Instant::now() + Duration::from_millis(rc.poll_timeout_ms.unwrap_or(60000) as u64);
+    // The routine may already be completed in the reply to its start, unless
+    // suppressed, and no status is received with a busyRepeatRequest NRC
+    let mut status_received = !ctxt.eval_expr.reply_bytes().is_empty();
+    loop {
+        let done = status_received
+            && poll_until
+                .compiled
+                .eval_boolean_with_context_mut(&mut ctxt.eval_expr.ctxt)
+                .map_err(|err| ScenarioError::EvalExpr(poll_until.str.clone(), err))?;
+        if done {
+            return Ok(());
+        }
+        let remain = deadline.saturating_duration_since(time::Instant::now());
+        if remain.is_zero() {
+            return Err(step_timeout(ctxt, "RoutineControl"));
+        }
+        // The last poll is sent at the deadline
+        let remain_ms = remain.as_micros().div_ceil(1000) as usize;
+        sleep_ms(ctxt, interval.min(remain_ms)).await?;
+        let request_results = parser::RoutineControlType::RequestResults.id();
+        status_received = match routine_request(ctxt, request_results, routine, &[], false).await {
+            // busyRepeatRequest
+            Err(ScenarioError::Nrc(0x21)) => false,
+            res => res.map(|()| true)?,
+        };
+    }
+}
+
+async fn routine_request(
+    ctxt: &mut Context,
+    control: u8,
+    routine: u16,
+    option: &[u8],
+    suppressed: bool,
+) -> Result<(), ScenarioError> {
+    let [routine_hi, routine_lo] = routine.to_be_bytes();
+    let subfunction = if suppressed { control | 0x80 } else { control };
+    let req = message::RawUds {
+        data: [&[0x31, subfunction, routine_hi, routine_lo], option].concat(),
+    };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply_unless_suppressed(ctxt, 0x31, suppressed)?;
+
+    // Positive response: 71 <control> <routine> <status record>
+    let reply = ctxt.eval_expr.reply_bytes();
+    if reply.is_empty() {
+        return Ok(());
+    }
+    if reply.get(1..4) != Some(&[control, routine_hi, routine_lo][..]) {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    Ok(())
+}
+
+async fn security_access(
+    ctxt: &mut Context,
+    sa: &parser::SecurityAccess,
+) -> Result<(), ScenarioError> {
+    if sa.level % 2 == 0 || sa.level > 0x7d {
+        return Err(ScenarioError::SeedKey(format!(
+            "security level {:#04x} isn't a valid requestSeed level",
+            sa.level
+        )));
+    }
+    let max_attempts = sa.max_attempts.unwrap_or(3);
+    let mut attempt = 1;
+    loop {
+        match unlock_security_level(ctxt, sa).await {
+            // requiredTimeDelayNotExpired
+            Err(ScenarioError::Nrc(0x37)) if attempt < max_attempts => {
+                let delay_ms = sa.delay_ms.unwrap_or(10000);
+                println!(
+                    "Security access delay not expired, requesting seed again in {delay_ms} ms."
+                );
+                sleep_ms(ctxt, delay_ms).await?;
+                attempt += 1;
+            }
+            // exceededNumberOfAttempts
+            Err(ScenarioError::Nrc(0x36)) => {
+                println!("Security access locked after too many invalid keys.");
+                return Err(ScenarioError::Nrc(0x36));
+            }
+            res => return res,
+        }
+    }
+}
+
+async fn unlock_security_level(
+    ctxt: &mut Context,
+    sa: &parser::SecurityAccess,
+) -> Result<(), ScenarioError> {
+    let req = message::RawUds {
+        data: vec![0x27, sa.level],
+    };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply(ctxt, 0x27)?;
+
+    // Positive response: 67 <level> <seed>
+    let reply = ctxt.eval_expr.reply_bytes();
+    if reply.get(1) != Some(&sa.level) {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    let seed = &reply[2..];
+    if seed.iter().all(|b| *b == 0) {
+        println!("Security level {:#04x} already unlocked.", sa.level);
+        return Ok(());
+    }
+
+    let key = seedkey::compute_key(&sa.key, seed, sa.level, ctxt.key_library.as_deref()).await?;
+    let mut data = vec![0x27, sa.level + 1];
+    data.extend(key);
+    let req = message::RawUds { data };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply(ctxt, 0x27)
+}
+
 async fn read_did(ctxt: &mut Context, rdid: &parser::ReadDID) -> Result<(), ScenarioError> {
-    let req = message::ReadDIDReq { did: rdid.did };
+    let req = message::ReadDIDReq {
+        did: ctxt.eval_expr.evaluate(&rdid.did)?,
+    };
     let uds = UdsMessage::ReadDIDReq(req);
     request_response(ctxt, uds).await
 }
 
+async fn read_dtc(ctxt: &mut Context, rdtc: &parser::ReadDTC) -> Result<(), ScenarioError> {
+    let request = rdtc.report.request();
+    let req = message::RawUds {
+        data: [&[0x19], &request[..]].concat(),
+    };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply(ctxt, 0x19)?;
+
+    let reply = ctxt.eval_expr.reply_bytes();
+    if reply.get(1) != request.first() {
+        return Err(ScenarioError::UnexpectedUdsMessage(
+            ctxt.last_uds_reply.clone(),
+        ));
+    }
+    dtc::print_reply(&reply);
+    Ok(())
+}
+
+async fn clear_dtc(ctxt: &mut Context, cdtc: &parser::ClearDTC) -> Result<(), ScenarioError> {
+    let group = cdtc.group.unwrap_or(0xffffff);
+    if group > 0xffffff {
+        return Err(ScenarioError::InvalidStep(
+            "ClearDTC".to_string(),
+            format!("group {group:#x} isn't between 0x0 and 0xffffff"),
+        ));
+    }
+    let group = group.to_be_bytes();
+    let req = message::RawUds {
+        data: [&[0x14], &group[1..]].concat(),
+    };
+    request_response(ctxt, UdsMessage::RawUds(req)).await?;
+    expect_reply(ctxt, 0x14)
+}
+
 async fn read_supported_dtc(
     ctxt: &mut Context,
     _rdtc: &parser::ReadSupportedDTC,
@@ -277,7 +1551,7 @@ async fn write_did(ctxt: &mut Context, wdid: &parser::WriteDID) -> Result<(), Sc
         .data
         .get_bytes(|varname| ctxt.eval_expr.get_tuple_variable(varname))?;
     let req = message::WriteDIDReq {
-        did: wdid.did,
+        did: ctxt.eval_expr.evaluate(&wdid.did)?,
         user_data,
     };
     let uds = UdsMessage::WriteDIDReq(req);
@@ -292,21 +1566,277 @@ fn eval_expr(ctxt: &mut Context, expr: &parser::EvalExpr) -> Result<(), Scenario
     Ok(())
 }
 
-async fn while_loop(ctxt: &mut Context, wl: &parser::WhileLoop) -> Result<bool, ScenarioError> {
-    let mut abort = false;
-    while !abort {
+async fn include(ctxt: &mut Context, inc: &parser::Include) -> Result<Flow, ScenarioError> {
+    let args = eval_args(ctxt, &inc.args)?;
+    ctxt.location.push(format!("include {}", inc.filename));
+    execute_scoped(ctxt, args, inc.local, &inc.steps).await
+}
+
+async fn call_procedure(ctxt: &mut Context, call: &parser::Call) -> Result<Flow, ScenarioError> {
+    let procedures = ctxt.procedures.clone();
+    let procedure = procedures.get(&call.procedure).ok_or_else(|| {
+        ScenarioError::InvalidCall(call.procedure.clone(), "unknown procedure".to_string())
+    })?;
+    call.check_args(&procedure.params)?;
+    // The location survives a call cut by a timeout, unlike a counter
+    let call_depth = ctxt
+        .location
+        .iter()
+        .filter(|location| location.starts_with("procedure "))
+        .count();
+    if call_depth == MAX_CALL_DEPTH {
+        return Err(ScenarioError::InvalidCall(
+            call.procedure.clone(),
+            format!("more than {MAX_CALL_DEPTH} nested calls"),
+        ));
+    }
+    let args = eval_args(ctxt, &call.args)?;
+    ctxt.location.push(format!("procedure {}", call.procedure));
+    execute_scoped(ctxt, args, call.local, &procedure.steps).await
+}
+
+fn eval_args(
+    ctxt: &mut Context,
+    args: &BTreeMap<String, parser::Expression>,
+) -> Result<Vec<(String, Value)>, ScenarioError> {
+    args.iter()
+        .map(|(name, expr)| {
+            expr.compiled
+                .eval_with_context_mut(&mut ctxt.eval_expr.ctxt)
+                .map(|value| (name.clone(), value))
+                .map_err(|err| ScenarioError::EvalExpr(expr.str.clone(), err))
+        })
+        .collect()
+}
+
+/// Execute steps with arguments bound to evalexpr variables. If the scope is
+/// local, all variables are restored afterwards.
+async fn execute_scoped(
+    ctxt: &mut Context,
+    args: Vec<(String, Value)>,
+    local: bool,
+    steps: &Vec<Step>,
+) -> Result<Flow, ScenarioError> {
+    let saved = local.then(|| ctxt.eval_expr.save_variables());
+    let res = match ctxt.eval_expr.set_variables(args) {
+        Ok(()) => execute_steps(ctxt, steps).await,
+        Err(err) => Err(err),
+    };
+    if let Some(saved) = saved {
+        ctxt.eval_expr.restore_variables(saved);
+    }
+    // A loop control doesn't leave a procedure or an included scenario
+    match res {
+        Ok(Flow::Break | Flow::Continue) => Ok(Flow::Next),
+        res => res,
+    }
+}
+
+/// Execute steps until they succeed, or until the last attempt.
+///
+/// An attempt fails if a step fails, or if the last reply is an NRC.
+async fn retry(ctxt: &mut Context, rt: &parser::Retry) -> Result<Flow, ScenarioError> {
+    let mut delay_ms = rt.delay_ms;
+    let mut attempt = 1;
+    loop {
+        let failure = match execute_steps(ctxt, &rt.steps).await {
+            Ok(Flow::Next) => match &ctxt.last_uds_reply {
+                UdsMessage::Nrc(nrc) => ScenarioError::Nrc(nrc.nrc),
+                _ => return Ok(Flow::Next),
+            },
+            Ok(flow) => return Ok(flow),
+            Err(err) => err,
+        };
+        if attempt >= rt.max_attempts.get() || !is_retryable(rt, &failure) {
+            return Err(failure);
+        }
+        println!(
+            "Attempt {attempt}/{} failed: {failure}, retrying in {delay_ms} ms",
+            rt.max_attempts
+        );
+        sleep_ms(ctxt, delay_ms).await?;
+        delay_ms = delay_ms.saturating_mul(rt.backoff_factor.unwrap_or(1));
+        attempt += 1;
+    }
+}
+
+/// Check a failure against both the retryable kinds of errors, an NRC being of
+/// the "Nrc" kind, and the retryable NRCs.
+fn is_retryable(rt: &parser::Retry, err: &ScenarioError) -> bool {
+    let retryable_kind = rt
+        .errors
+        .as_ref()
+        .is_none_or(|errors| errors.iter().any(|kind| kind == err.kind()));
+    let retryable_nrc = match err {
+        ScenarioError::Nrc(nrc) => rt.nrcs.as_ref().is_none_or(|nrcs| nrcs.contains(nrc)),
+        _ => true,
+    };
+    retryable_kind && retryable_nrc
+}
+
+async fn try_steps(ctxt: &mut Context, tr: &parser::Try) -> Result<Flow, ScenarioError> {
+    let mut res = execute_steps(ctxt, &tr.steps).await;
+    if let (Err(err), Some(on_error)) = (&res, &tr.on_error) {
+        println!("Scenario error caught: {err}");
+        ctxt.eval_expr.set_error(err);
+        res = execute_steps(ctxt, on_error).await;
+    }
+    if let Some(finally) = &tr.finally {
+        let finally_res = execute_steps(ctxt, finally).await;
+        res = match (res, finally_res) {
+            (Err(err), _) | (Ok(_), Err(err)) => Err(err),
+            (Ok(flow), Ok(Flow::Next)) => Ok(flow),
+            (Ok(_), Ok(finally_flow)) => Ok(finally_flow),
+        };
+    }
+    res
+}
+
+async fn while_loop(
+    ctxt: &mut Context,
+    wl: &parser::WhileLoop,
+    top_step: Option<TopStep>,
+) -> Result<Flow, ScenarioError> {
+    // A resumed while loop goes on from its condition, with the restored variables
+    let mut iteration = top_step.map_or(0, |top_step| top_step.completed_iterations);
+    loop {
         let cond = wl
             .condition
             .compiled
             .eval_boolean_with_context_mut(&mut ctxt.eval_expr.ctxt)
             .map_err(|err| ScenarioError::EvalExpr(wl.condition.str.clone(), err))?;
-        if cond {
-            abort = execute_steps(ctxt, &wl.steps).await?;
-        } else {
+        if !cond {
             break;
         }
+        match execute_steps(ctxt, &wl.steps).await? {
+            Flow::Break => break,
+            Flow::Abort => return Ok(Flow::Abort),
+            Flow::Next | Flow::Continue => {}
+        }
+        iteration += 1;
+        write_loop_checkpoint(ctxt, top_step, iteration)?;
+    }
+    Ok(Flow::Next)
+}
+
+async fn for_each(
+    ctxt: &mut Context,
+    fe: &parser::ForEach,
+    top_step: Option<TopStep>,
+) -> Result<Flow, ScenarioError> {
+    // A range is iterated lazily, without allocating all its items
+    let items: Box<dyn Iterator<Item = Value>> = match &fe.items {
+        parser::ForEachItems::Tuple(expr) => {
+            match expr
+                .compiled
+                .eval_with_context_mut(&mut ctxt.eval_expr.ctxt)
+                .map_err(|err| ScenarioError::EvalExpr(expr.str.clone(), err))?
+            {
+                Value::Tuple(values) => Box::new(values.into_iter()),
+                value => Box::new(std::iter::once(value)),
+            }
+        }
+        parser::ForEachItems::Range(range) => {
+            let step = range.step.map_or(1, |step| step.get());
+            let end = range.end;
+            let range = std::iter::successors(Some(range.start), move |i| i.checked_add(step))
+                .take_while(move |i| if step > 0 { *i < end } else { *i > end })
+                .map(Value::Int);
+            Box::new(range)
+        }
+    };
+
+    let first = top_step.map_or(0, |top_step| top_step.completed_iterations);
+    for (iteration, item) in items.enumerate().skip(first) {
+        ctxt.eval_expr.set_variable(&fe.variable, item)?;
+        match execute_steps(ctxt, &fe.steps).await? {
+            Flow::Break => break,
+            Flow::Abort => return Ok(Flow::Abort),
+            Flow::Next | Flow::Continue => {}
+        }
+        write_loop_checkpoint(ctxt, top_step, iteration + 1)?;
+    }
+    Ok(Flow::Next)
+}
+
+async fn repeat(
+    ctxt: &mut Context,
+    rp: &parser::Repeat,
+    top_step: Option<TopStep>,
+) -> Result<Flow, ScenarioError> {
+    let first = top_step.map_or(0, |top_step| top_step.completed_iterations);
+    for i in first..rp.count {
+        if let Some(variable) = &rp.variable {
+            ctxt.eval_expr
+                .set_variable(variable, Value::Int(i as i64))?;
+        }
+        match execute_steps(ctxt, &rp.steps).await? {
+            Flow::Break => break,
+            Flow::Abort => return Ok(Flow::Abort),
+            Flow::Next | Flow::Continue => {}
+        }
+        write_loop_checkpoint(ctxt, top_step, i + 1)?;
+    }
+    Ok(Flow::Next)
+}
+
+async fn if_else(ctxt: &mut Context, cond: &parser::If) -> Result<Flow, ScenarioError> {
+    let test = cond
+        .condition
+        .compiled
+        .eval_boolean_with_context_mut(&mut ctxt.eval_expr.ctxt)
+        .map_err(|err| ScenarioError::EvalExpr(cond.condition.str.clone(), err))?;
+    if test {
+        execute_steps(ctxt, &cond.then).await
+    } else if let Some(otherwise) = &cond.otherwise {
+        execute_steps(ctxt, otherwise).await
+    } else {
+        Ok(Flow::Next)
+    }
+}
+
+/// Flow of a Break or a Continue, which is only taken if its optional
+/// condition is true.
+fn loop_control(
+    ctxt: &mut Context,
+    condition: &Option<parser::Expression>,
+    flow: Flow,
+) -> Result<Flow, ScenarioError> {
+    let taken = match condition {
+        Some(condition) => condition
+            .compiled
+            .eval_boolean_with_context_mut(&mut ctxt.eval_expr.ctxt)
+            .map_err(|err| ScenarioError::EvalExpr(condition.str.clone(), err))?,
+        None => true,
+    };
+    Ok(if taken { flow } else { Flow::Next })
+}
+
+/// Conversion of an evalexpr result into the type of a step field.
+trait FromValue: Sized {
+    const KIND: &'static str;
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for u16 {
+    const KIND: &'static str = "16 bits unsigned integer";
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_int().ok().and_then(|i| u16::try_from(i).ok())
+    }
+}
+
+impl FromValue for usize {
+    const KIND: &'static str = "positive integer";
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_int().ok().and_then(|i| usize::try_from(i).ok())
+    }
+}
+
+impl FromValue for String {
+    const KIND: &'static str = "string";
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_string().ok()
     }
-    Ok(abort)
 }
 
 struct EvalExprContext {
@@ -315,6 +1845,9 @@ struct EvalExprContext {
 }
 
 impl EvalExprContext {
+    /// Variables reflecting the ECU state rather than the scenario state
+    const ECU_STATE_VARIABLES: [&'static str; 2] = ["reply", "session"];
+
     pub fn new() -> Self {
         let reply = Arc::new(Mutex::new(Vec::<u8>::new()));
         let reply_c1 = reply.clone();
@@ -357,6 +1890,34 @@ impl EvalExprContext {
         Self { ctxt, reply }
     }
 
+    /// Expose the seed-key library to the expressions, through the function
+    /// generate_key(seed, level, variant) returning the key.
+    pub fn set_key_library(
+        &mut self,
+        key_library: Arc<seedkey::KeyLibrary>,
+    ) -> Result<(), ScenarioError> {
+        use evalexpr::ContextWithMutableFunctions;
+        let generate_key = Function::new(move |argument| {
+            let arguments = argument.as_fixed_len_tuple(3)?;
+            let seed = match &arguments[0] {
+                Value::Tuple(seed) => Self::value_to_bytes(seed),
+                seed => return Err(EvalexprError::expected_tuple(seed.clone())),
+            };
+            let level = u8::try_from(arguments[1].as_int()?)
+                .map_err(|_| EvalexprError::CustomMessage("Invalid security level".to_string()))?;
+            let variant = arguments[2].as_string()?;
+            let key = key_library
+                .generate_key(&seed, level, &variant)
+                .map_err(|err| EvalexprError::CustomMessage(err.to_string()))?;
+            Ok(Value::Tuple(
+                key.iter().map(|b| Value::Int(*b as i64)).collect(),
+            ))
+        });
+        self.ctxt
+            .set_function("generate_key".to_string(), generate_key)
+            .map_err(|err| ScenarioError::EvalExpr("generate_key".to_string(), err))
+    }
+
     pub fn set_reply(&mut self, uds_reply: &UdsMessage) {
         let mut reply: Vec<u8> = vec![];
         uds_write(&mut reply, uds_reply).unwrap();
@@ -367,6 +1928,91 @@ impl EvalExprContext {
         *self.reply.lock().unwrap() = reply;
     }
 
+    pub fn set_variable(&mut self, varname: &str, value: Value) -> Result<(), ScenarioError> {
+        self.ctxt
+            .set_value(varname.to_string(), value)
+            .map_err(|err| ScenarioError::EvalExpr(varname.to_string(), err))
+    }
+
+    pub fn set_variables(&mut self, variables: Vec<(String, Value)>) -> Result<(), ScenarioError> {
+        for (name, value) in variables.into_iter() {
+            self.set_variable(&name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Expose an error to the evalexpr expressions, through the variables
+    /// "error_kind", "error_nrc" (0 if the error is not an NRC) and
+    /// "error_message".
+    pub fn set_error(&mut self, err: &ScenarioError) {
+        let nrc = if let ScenarioError::Nrc(nrc) = err {
+            *nrc
+        } else {
+            0
+        };
+        let _ = self.ctxt.set_value(
+            "error_kind".to_string(),
+            Value::String(err.kind().to_string()),
+        );
+        let _ = self
+            .ctxt
+            .set_value("error_nrc".to_string(), Value::Int(nrc as i64));
+        let _ = self
+            .ctxt
+            .set_value("error_message".to_string(), Value::String(err.to_string()));
+    }
+
+    /// Snapshot of all variables, to be restored by restore_variables().
+    pub fn save_variables(&self) -> Vec<(String, Value)> {
+        use evalexpr::IterateVariablesContext;
+        self.ctxt.iter_variables().collect()
+    }
+
+    /// Restore all variables to a previous snapshot, except for the variables
+    /// reflecting the ECU state, which keep their current value.
+    pub fn restore_variables(&mut self, variables: Vec<(String, Value)>) {
+        use evalexpr::Context;
+        let ecu_state: Vec<(&str, Option<Value>)> = Self::ECU_STATE_VARIABLES
+            .iter()
+            .map(|name| (*name, self.ctxt.get_value(name).cloned()))
+            .collect();
+        self.ctxt.clear_variables();
+        for (name, value) in variables.into_iter() {
+            let _ = self.ctxt.set_value(name, value);
+        }
+        for (name, value) in ecu_state.into_iter() {
+            if let Some(value) = value {
+                let _ = self.ctxt.set_value(name.to_string(), value);
+            }
+        }
+    }
+
+    /// Value of a step field, evaluating its expression if it has one.
+    pub fn evaluate<T: FromValue + Clone>(
+        &mut self,
+        field: &parser::Evaluable<T>,
+    ) -> Result<T, ScenarioError> {
+        match field {
+            parser::Evaluable::Value(value) => Ok(value.clone()),
+            parser::Evaluable::Expr(expr) => {
+                let value = expr
+                    .compiled
+                    .eval_with_context_mut(&mut self.ctxt)
+                    .map_err(|err| ScenarioError::EvalExpr(expr.str.clone(), err))?;
+                T::from_value(&value).ok_or_else(|| {
+                    ScenarioError::EvalExpr(
+                        expr.str.clone(),
+                        EvalexprError::CustomMessage(format!("{value} is not a {}", T::KIND)),
+                    )
+                })
+            }
+        }
+    }
+
+    pub fn reply_bytes(&self) -> Vec<u8> {
+        self.reply.lock().unwrap().clone()
+    }
+
     pub fn get_tuple_variable(&self, varname: &str) -> Result<Vec<u8>, io::Error> {
         use evalexpr::Context;
         self.ctxt
@@ -1,31 +1,65 @@
+use super::checkpoint::Checkpoint;
 use super::error::ScenarioError;
 use super::parser;
 use doip_rw::LogicalAddress;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 use super::doip_ops;
 
+/// How a scenario finished, when it finished without error.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Success,
+    /// Some assertions failed, but the scenario went on until its end
+    AssertionsFailed(usize),
+    /// The scenario was aborted by a step, such as AbortIfNrc
+    Aborted,
+}
+
+/// Settings of the execution of a scenario.
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// Maximum duration of the whole scenario
+    pub timeout: Option<Duration>,
+    /// File where a checkpoint is written after each top-level step
+    pub checkpoint_file: Option<PathBuf>,
+    /// Checkpoint of an interrupted run, to resume the scenario from
+    pub resume: Option<Checkpoint>,
+    /// Shared library computing the SecurityAccess keys
+    pub seedkey_library: Option<PathBuf>,
+    /// Period of the TesterPresent sent automatically in the non-default
+    /// sessions
+    pub tester_present: Option<Duration>,
+}
+
 pub async fn scenario(
     local_addr: SocketAddr,
     remote_addr: SocketAddr,
     la: LogicalAddress,
     ta: LogicalAddress,
-    steps: parser::Steps,
-) -> Result<(), ScenarioError> {
+    scenario: parser::Scenario,
+    parameters: BTreeMap<String, parser::ParamValue>,
+    settings: Settings,
+) -> Result<Outcome, ScenarioError> {
+    let parameters = scenario.bind_parameters(&parameters)?;
     let (req_tx, mut req_rx) = mpsc::channel(1);
     let (rsp_tx, rsp_rx) = mpsc::channel(3); // 2 because the Notifications can come in burst
 
     let mut doip_cnx = doip_ops::DoIpConnection::connect(local_addr, remote_addr, la).await?;
 
     tokio::spawn(async move {
+        let mut keep_alive = doip_ops::KeepAlive::default();
         loop {
             #[rustfmt::skip]
             tokio::select! {
-                rscen = doip_cnx.recv_scenario() => {
+                rscen = doip_cnx.recv_scenario(), if doip_cnx.is_connected() => {
                     match rscen {
 			Ok(scen) => {
-                            if rsp_tx.send(scen).await.is_err() {
+                            if keep_alive.on_reply(&scen) && rsp_tx.send(scen).await.is_err() {
                                 break;
                             }
 			},
@@ -40,16 +74,35 @@ pub async fn scenario(
                     }
                 },
                 Some(scen_req) = req_rx.recv() => {
-                    if doip_cnx.send_scenario(ta, scen_req)
+                    if let doip_ops::ScenarioMessage::KeepAlive(period) = scen_req {
+                        keep_alive.set_period(period);
+                        continue;
+                    }
+                    keep_alive.on_request(&scen_req);
+                    let reconnect = matches!(scen_req, doip_ops::ScenarioMessage::ReconnectReq { .. });
+                    match doip_cnx.send_scenario(ta, scen_req).await {
+                        Ok(()) => {},
+                        Err(err) if reconnect => {
+                            let failed = doip_ops::ScenarioMessage::NotifyReconnectFailed(err);
+                            if rsp_tx.send(failed).await.is_err() {
+                                break;
+                            }
+                        },
+                        Err(_) => break,
+                    }
+                },
+                tester_present = keep_alive.tick(), if keep_alive.is_enabled() && doip_cnx.is_connected() => {
+                    if doip_cnx.send_scenario(ta, tester_present)
                         .await
                         .is_err()
                     {
                         break;
                     }
                 },
+                else => break,
             };
         }
     });
 
-    super::executor::execute(steps, req_tx, rsp_rx).await
+    super::executor::execute(scenario, parameters, settings, req_tx, rsp_rx).await
 }
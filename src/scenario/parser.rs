@@ -1,10 +1,16 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{self, Read},
+    num::{NonZeroI64, NonZeroUsize},
+    path::Path,
 };
 
 use serde::{Deserialize, Serialize};
 
+use super::error::ScenarioError;
+pub use evalexpression::Expression;
+
 pub type Steps = Vec<Step>;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -15,68 +21,614 @@ pub enum RawBytes {
     EvalExprVarname(String),
 }
 
-#[derive(Serialize, Deserialize, PartialEq)]
-struct Scenario {
-    steps: Steps,
+/// Field given either as a literal value, or as an evalexpr expression
+/// evaluated when the step is executed, such as `did: !Expr "base_did + i"`.
+#[derive(Debug, PartialEq)]
+pub enum Evaluable<T> {
+    Value(T),
+    Expr(evalexpression::Expression),
+}
+
+pub type Procedures = BTreeMap<String, Procedure>;
+
+/// Declared parameters of a scenario, with their default value, or None if the
+/// parameter is required.
+pub type Parameters = BTreeMap<String, Option<ParamValue>>;
+
+/// Value of a scenario parameter, such as given on the command line.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Int(i64),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+/// A scenario document.
+///
+/// A scenario file is either a list of steps, or a document holding the
+/// procedures definitions next to the steps.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Scenario {
+    #[serde(default)]
+    pub parameters: Parameters,
+    #[serde(default)]
+    pub procedures: Procedures,
+    pub steps: Steps,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Procedure {
+    #[serde(default)]
+    pub params: Vec<String>,
+    pub steps: Steps,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum Step {
     AbortIfNrc(AbortIfNrc),
+    Break(Break),
+    Call(Call),
+    ClearDTC(ClearDTC),
+    CommunicationControl(CommunicationControl),
+    Continue(Continue),
+    ControlDTCSetting(ControlDTCSetting),
+    DiagnosticSession(DiagnosticSession),
     DisconnectDoIp(DisconnectDoIp),
+    EcuReset(EcuReset),
     EvalExpr(EvalExpr),
+    ExpectReply(ExpectReply),
+    ForEach(ForEach),
+    If(If),
+    Include(Include),
+    IoControl(IoControl),
     PrintLastReply,
     RawUds(RawUds),
     ReadDID(ReadDID),
+    ReadDTC(ReadDTC),
+    ReadMemoryByAddress(ReadMemoryByAddress),
     ReadSupportedDTC(ReadSupportedDTC),
-    SleepMs(usize),
+    Repeat(Repeat),
+    Retry(Retry),
+    RoutineControl(RoutineControl),
+    SecurityAccess(SecurityAccess),
+    SleepMs(#[serde(deserialize_with = "evaluable::untagged")] Evaluable<usize>),
+    TesterPresent(TesterPresent),
+    Try(Try),
     WhileLoop(WhileLoop),
     WriteDID(WriteDID),
+    WriteMemoryByAddress(WriteMemoryByAddress),
     TransferDownload(TransferDownload),
+    TransferUpload(TransferUpload),
+}
+
+/// Options common to all the steps, flattened into the fields of each step.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct StepOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct AbortIfNrc {
     pub nrc: Option<u8>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Leave the innermost loop, if the optional condition is true.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Break {
+    #[serde(default, with = "evalexpression::option")]
+    pub condition: Option<evalexpression::Expression>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Call {
+    pub procedure: String,
+    #[serde(default, with = "evalexpression::map")]
+    pub args: BTreeMap<String, evalexpression::Expression>,
+    #[serde(default)]
+    pub local: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+impl Call {
+    /// Check that the arguments of the call are the parameters of the
+    /// procedure.
+    pub fn check_args(&self, params: &[String]) -> Result<(), ScenarioError> {
+        if params.len() != self.args.len() || !params.iter().all(|p| self.args.contains_key(p)) {
+            return Err(ScenarioError::InvalidCall(
+                self.procedure.clone(),
+                format!("expected arguments ({})", params.join(", ")),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Clear the DTCs of a group, all the DTCs by default.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ClearDTC {
+    /// 3-byte group of DTC, 0xffffff for all groups
+    pub group: Option<u32>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Enable or disable the transmission and reception of messages on the
+/// networks of the ECU.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CommunicationControl {
+    pub control: CommunicationControlType,
+    /// Messages controlled, the normal communication messages by default
+    pub communication: Option<CommunicationType>,
+    /// Subnet controlled, from 0x1 to 0xe, or 0xf for the subnet the request
+    /// is received on, all the subnets by default
+    pub subnet: Option<u8>,
+    /// Send the request with the suppressPosRspMsgIndicationBit
+    #[serde(default)]
+    pub suppress_response: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum CommunicationControlType {
+    EnableRxAndTx,
+    EnableRxAndDisableTx,
+    DisableRxAndEnableTx,
+    DisableRxAndTx,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum CommunicationType {
+    Normal,
+    NetworkManagement,
+    NormalAndNetworkManagement,
+}
+
+/// Go on with the next iteration of the innermost loop, if the optional
+/// condition is true.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Continue {
+    #[serde(default, with = "evalexpression::option")]
+    pub condition: Option<evalexpression::Expression>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Stop or resume the update of the DTC status bits by the ECU.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ControlDTCSetting {
+    pub setting: DTCSetting,
+    /// Send the request with the suppressPosRspMsgIndicationBit
+    #[serde(default)]
+    pub suppress_response: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum DTCSetting {
+    On,
+    Off,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DiagnosticSession {
+    pub session: Session,
+    /// Send the request with the suppressPosRspMsgIndicationBit
+    #[serde(default)]
+    pub suppress_response: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Diagnostic session, such as `Extended`, or `!Oem 0x40` for a vehicle
+/// manufacturer specific session.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum Session {
+    Default,
+    Programming,
+    Extended,
+    SafetySystem,
+    Oem(u8),
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct DisconnectDoIp {
     pub wait_after_ms: Option<usize>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Reset the ECU, and reconnect once it is back.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct EcuReset {
+    pub reset_type: ResetType,
+    /// Send the request with the suppressPosRspMsgIndicationBit
+    #[serde(default)]
+    pub suppress_response: bool,
+    /// Maximum time for the ECU to accept a new connection, 30000ms by default
+    pub max_wait_ms: Option<usize>,
+    /// Delay before reconnecting, for the ECU to go down first
+    pub reset_delay_ms: Option<usize>,
+    /// Wait for the vehicle announcement of the ECU before reconnecting
+    #[serde(default)]
+    pub wait_announcement: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Reset type, such as `Hard`, or `!Oem 0x40` for a vehicle manufacturer
+/// specific reset.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ResetType {
+    Hard,
+    KeyOffOn,
+    Soft,
+    Oem(u8),
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct EvalExpr {
     #[serde(with = "evalexpression")]
     pub expression: evalexpression::Expression,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExpectReply {
+    pub starts_with: Option<RawBytes>,
+    pub bytes: Option<Vec<ExpectByte>>,
+    /// Regex matched against the lowercase hexadecimal reply, such as "62f190"
+    #[serde(default, with = "replyregex::option")]
+    pub regex: Option<replyregex::ReplyRegex>,
+    pub on_failure: Option<OnFailure>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExpectByte {
+    pub index: usize,
+    pub value: u8,
+    pub mask: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum OnFailure {
+    Abort,
+    Continue,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ForEach {
+    pub variable: String,
+    pub items: ForEachItems,
+    pub steps: Steps,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum ForEachItems {
+    #[serde(with = "evalexpression")]
+    Tuple(evalexpression::Expression),
+    Range(Range),
+}
+
+/// Integer range, start included, end excluded.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: Option<NonZeroI64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct If {
+    #[serde(with = "evalexpression")]
+    pub condition: evalexpression::Expression,
+    pub then: Steps,
+    #[serde(rename = "else")]
+    pub otherwise: Option<Steps>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Include {
+    pub filename: String,
+    #[serde(default, with = "evalexpression::map")]
+    pub args: BTreeMap<String, evalexpression::Expression>,
+    #[serde(default)]
+    pub local: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+    /// Steps of the included file, loaded when the scenario is read.
+    #[serde(skip)]
+    pub steps: Steps,
+}
+
+/// Take control of an input or output of the ECU, or return it to the ECU.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct IoControl {
+    pub did: Evaluable<u16>,
+    pub control: IoControlType,
+    /// Control state, such as the value of a ShortTermAdjustment
+    pub state: Option<RawBytes>,
+    /// Control enable mask, selecting the signals of the identifier
+    pub mask: Option<RawBytes>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum IoControlType {
+    ReturnControlToEcu,
+    ResetToDefault,
+    FreezeCurrentState,
+    ShortTermAdjustment,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct RawUds {
     pub data: RawBytes,
+    #[serde(flatten)]
+    pub options: StepOptions,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct ReadDID {
-    pub did: u16,
+    pub did: Evaluable<u16>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Read DTC information, and print the decoded DTCs.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReadDTC {
+    pub report: DTCReport,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Report of a ReadDTCInformation request.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum DTCReport {
+    /// Number of DTCs matching a status mask
+    NumberByStatusMask(u8),
+    /// DTCs matching a status mask
+    ByStatusMask(u8),
+    /// Snapshot record of a DTC, 0xff for all records
+    Snapshot {
+        #[serde(with = "dtc_number")]
+        dtc: u32,
+        record: u8,
+    },
+    /// Extended data record of a DTC, 0xff for all records
+    ExtendedData {
+        #[serde(with = "dtc_number")]
+        dtc: u32,
+        record: u8,
+    },
+    /// All the DTCs supported by the ECU
+    Supported,
+}
+
+/// Read a memory area of the ECU, split into requests of at most chunk_size
+/// bytes.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReadMemoryByAddress {
+    pub addr: Evaluable<usize>,
+    pub size: Evaluable<usize>,
+    /// Number of bytes of the address in the requests, 4 by default
+    pub address_bytes: Option<u8>,
+    /// Number of bytes of the size in the requests, 2 by default
+    pub size_bytes: Option<u8>,
+    /// Maximum size of data of a request, 256 by default
+    pub chunk_size: Option<usize>,
+    /// File where the read data are written
+    pub filename: Option<Evaluable<String>>,
+    /// Evalexpr variable where the read data are stored, as a tuple of bytes
+    pub variable: Option<String>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReadSupportedDTC {
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Repeat {
+    pub count: usize,
+    pub variable: Option<String>,
+    pub steps: Steps,
+    #[serde(flatten)]
+    pub options: StepOptions,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
-pub struct ReadSupportedDTC {}
+pub struct Retry {
+    /// Number of attempts, at least 1
+    pub max_attempts: NonZeroUsize,
+    pub delay_ms: usize,
+    /// Multiplier of the delay after each failed attempt
+    pub backoff_factor: Option<usize>,
+    /// Retryable NRCs, all if None
+    pub nrcs: Option<Vec<u8>>,
+    /// Retryable kinds of errors, such as "NetworkConnectorDead", all if None
+    pub errors: Option<Vec<String>>,
+    pub steps: Steps,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Start or stop a routine, or request its results, optionally polling its
+/// results until a condition is met.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RoutineControl {
+    pub control: RoutineControlType,
+    pub routine: Evaluable<u16>,
+    /// Option record of the request
+    pub option: Option<RawBytes>,
+    /// Send the request with the suppressPosRspMsgIndicationBit
+    #[serde(default)]
+    pub suppress_response: bool,
+    /// Condition on the reply of the requestResults, such as
+    /// "reply_nth(4) == 0", polled until it is true
+    #[serde(default, with = "evalexpression::option")]
+    pub poll_until: Option<evalexpression::Expression>,
+    /// Time between two requestResults, 100ms by default
+    pub poll_interval_ms: Option<usize>,
+    /// Maximum polling time, 60000ms by default
+    pub poll_timeout_ms: Option<usize>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum RoutineControlType {
+    Start,
+    Stop,
+    RequestResults,
+}
+
+/// Unlock a security level through requestSeed and sendKey.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SecurityAccess {
+    /// requestSeed subfunction, odd, the sendKey subfunction being level + 1
+    pub level: u8,
+    pub key: KeySource,
+    /// Number of requestSeed attempts while the ECU replies NRC 0x37
+    pub max_attempts: Option<usize>,
+    /// Time to wait after a NRC 0x37 before a new requestSeed
+    pub delay_ms: Option<usize>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Computation of the key from the seed.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub enum KeySource {
+    Command(KeyCommand),
+    Plugin(KeyPlugin),
+}
+
+/// External program computing the key, which prints it in hexadecimal on its
+/// standard output.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct KeyCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub seed: SeedInput,
+}
+
+/// GenerateKeyEx function of the seed-key library of the configuration.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct KeyPlugin {
+    /// Variant string given to the library
+    #[serde(default)]
+    pub variant: String,
+    /// Expected length of the key
+    pub key_length: Option<usize>,
+}
+
+/// How the seed is given in hexadecimal to the key computation program.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SeedInput {
+    /// On a line of the standard input
+    #[default]
+    Stdin,
+    /// As the last argument
+    Argument,
+}
+
+/// Start sending TesterPresent periodically, to keep the current session
+/// alive, or stop sending it.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TesterPresent {
+    /// Period of the TesterPresent, 2000ms by default
+    pub period_ms: Option<usize>,
+    #[serde(default)]
+    pub stop: bool,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Try {
+    pub steps: Steps,
+    pub on_error: Option<Steps>,
+    pub finally: Option<Steps>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct WriteDID {
-    pub did: u16,
+    pub did: Evaluable<u16>,
     pub data: RawBytes,
+    #[serde(flatten)]
+    pub options: StepOptions,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct TransferDownload {
     pub compression_method: u8,
     pub encrypt_method: u8,
-    pub addr: usize,
-    pub filename: String,
-    pub memorysize: usize,
+    pub addr: Evaluable<usize>,
+    pub filename: Evaluable<String>,
+    pub memorysize: Evaluable<usize>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Write a memory area of the ECU, split into requests of at most chunk_size
+/// bytes.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct WriteMemoryByAddress {
+    pub addr: Evaluable<usize>,
+    pub data: RawBytes,
+    /// Number of bytes of the address in the requests, 4 by default
+    pub address_bytes: Option<u8>,
+    /// Number of bytes of the size in the requests, 2 by default
+    pub size_bytes: Option<u8>,
+    /// Maximum size of data of a request, 256 by default
+    pub chunk_size: Option<usize>,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+/// Read a memory area of the ECU through RequestUpload, TransferData and
+/// RequestTransferExit.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransferUpload {
+    pub compression_method: u8,
+    pub encrypt_method: u8,
+    pub addr: Evaluable<usize>,
+    pub memorysize: Evaluable<usize>,
+    /// Number of bytes of the address in the request, 4 by default
+    pub address_bytes: Option<u8>,
+    /// Number of bytes of the size in the request, 4 by default
+    pub size_bytes: Option<u8>,
+    /// File where the uploaded data are written
+    pub filename: Option<Evaluable<String>>,
+    /// Evalexpr variable where the uploaded data are stored, as a tuple of bytes
+    pub variable: Option<String>,
+    #[serde(flatten)]
+    pub options: StepOptions,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -84,33 +636,574 @@ pub struct WhileLoop {
     #[serde(with = "evalexpression")]
     pub condition: evalexpression::Expression,
     pub steps: Steps,
+    #[serde(flatten)]
+    pub options: StepOptions,
+}
+
+impl Step {
+    /// Lists of steps nested in this step, such as the body of a loop.
+    fn substeps_mut(&mut self) -> Vec<&mut Steps> {
+        match self {
+            Step::ForEach(fe) => vec![&mut fe.steps],
+            Step::If(cond) => {
+                let mut substeps = vec![&mut cond.then];
+                if let Some(otherwise) = &mut cond.otherwise {
+                    substeps.push(otherwise);
+                }
+                substeps
+            }
+            Step::Include(inc) => vec![&mut inc.steps],
+            Step::Repeat(rp) => vec![&mut rp.steps],
+            Step::Retry(rt) => vec![&mut rt.steps],
+            Step::Try(tr) => {
+                let mut substeps = vec![&mut tr.steps];
+                if let Some(on_error) = &mut tr.on_error {
+                    substeps.push(on_error);
+                }
+                if let Some(finally) = &mut tr.finally {
+                    substeps.push(finally);
+                }
+                substeps
+            }
+            Step::WhileLoop(wl) => vec![&mut wl.steps],
+            _ => vec![],
+        }
+    }
+
+    /// Keyword of the step, such as "ReadDID".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::AbortIfNrc(_) => "AbortIfNrc",
+            Step::Break(_) => "Break",
+            Step::Call(_) => "Call",
+            Step::ClearDTC(_) => "ClearDTC",
+            Step::CommunicationControl(_) => "CommunicationControl",
+            Step::Continue(_) => "Continue",
+            Step::ControlDTCSetting(_) => "ControlDTCSetting",
+            Step::DiagnosticSession(_) => "DiagnosticSession",
+            Step::DisconnectDoIp(_) => "DisconnectDoIp",
+            Step::EcuReset(_) => "EcuReset",
+            Step::EvalExpr(_) => "EvalExpr",
+            Step::ExpectReply(_) => "ExpectReply",
+            Step::ForEach(_) => "ForEach",
+            Step::If(_) => "If",
+            Step::Include(_) => "Include",
+            Step::IoControl(_) => "IoControl",
+            Step::PrintLastReply => "PrintLastReply",
+            Step::RawUds(_) => "RawUds",
+            Step::ReadDID(_) => "ReadDID",
+            Step::ReadDTC(_) => "ReadDTC",
+            Step::ReadMemoryByAddress(_) => "ReadMemoryByAddress",
+            Step::ReadSupportedDTC(_) => "ReadSupportedDTC",
+            Step::Repeat(_) => "Repeat",
+            Step::Retry(_) => "Retry",
+            Step::RoutineControl(_) => "RoutineControl",
+            Step::SecurityAccess(_) => "SecurityAccess",
+            Step::SleepMs(_) => "SleepMs",
+            Step::TesterPresent(_) => "TesterPresent",
+            Step::Try(_) => "Try",
+            Step::WhileLoop(_) => "WhileLoop",
+            Step::WriteDID(_) => "WriteDID",
+            Step::WriteMemoryByAddress(_) => "WriteMemoryByAddress",
+            Step::TransferDownload(_) => "TransferDownload",
+            Step::TransferUpload(_) => "TransferUpload",
+        }
+    }
+
+    /// Maximum duration of the step, nested steps included.
+    pub fn timeout_ms(&self) -> Option<usize> {
+        self.options().and_then(|options| options.timeout_ms)
+    }
+
+    /// Options of the step, common to all the steps having fields.
+    pub fn options(&self) -> Option<&StepOptions> {
+        match self {
+            Step::AbortIfNrc(anrc) => Some(&anrc.options),
+            Step::Break(brk) => Some(&brk.options),
+            Step::Call(call) => Some(&call.options),
+            Step::ClearDTC(cdtc) => Some(&cdtc.options),
+            Step::CommunicationControl(cc) => Some(&cc.options),
+            Step::Continue(cont) => Some(&cont.options),
+            Step::ControlDTCSetting(cdtcs) => Some(&cdtcs.options),
+            Step::DiagnosticSession(ds) => Some(&ds.options),
+            Step::DisconnectDoIp(disc) => Some(&disc.options),
+            Step::EcuReset(er) => Some(&er.options),
+            Step::EvalExpr(expr) => Some(&expr.options),
+            Step::ExpectReply(er) => Some(&er.options),
+            Step::ForEach(fe) => Some(&fe.options),
+            Step::If(cond) => Some(&cond.options),
+            Step::Include(inc) => Some(&inc.options),
+            Step::IoControl(ioc) => Some(&ioc.options),
+            Step::PrintLastReply => None,
+            Step::RawUds(ruds) => Some(&ruds.options),
+            Step::ReadDID(did) => Some(&did.options),
+            Step::ReadDTC(rdtc) => Some(&rdtc.options),
+            Step::ReadMemoryByAddress(rmba) => Some(&rmba.options),
+            Step::ReadSupportedDTC(dtc) => Some(&dtc.options),
+            Step::Repeat(rp) => Some(&rp.options),
+            Step::Retry(rt) => Some(&rt.options),
+            Step::RoutineControl(rc) => Some(&rc.options),
+            Step::SecurityAccess(sa) => Some(&sa.options),
+            Step::SleepMs(_) => None,
+            Step::TesterPresent(tp) => Some(&tp.options),
+            Step::Try(tr) => Some(&tr.options),
+            Step::WhileLoop(wl) => Some(&wl.options),
+            Step::WriteDID(did) => Some(&did.options),
+            Step::WriteMemoryByAddress(wmba) => Some(&wmba.options),
+            Step::TransferDownload(td) => Some(&td.options),
+            Step::TransferUpload(tu) => Some(&tu.options),
+        }
+    }
+}
+
+impl Session {
+    /// Identifier of the session, sent in a DiagnosticSessionControl request.
+    pub fn id(&self) -> u8 {
+        match self {
+            Session::Default => 0x01,
+            Session::Programming => 0x02,
+            Session::Extended => 0x03,
+            Session::SafetySystem => 0x04,
+            Session::Oem(id) => *id,
+        }
+    }
+}
+
+impl DTCReport {
+    /// Request of the report, without its service id.
+    pub fn request(&self) -> Vec<u8> {
+        let dtc_bytes = |dtc: &u32| dtc.to_be_bytes()[1..].to_vec();
+        match self {
+            DTCReport::NumberByStatusMask(mask) => vec![0x01, *mask],
+            DTCReport::ByStatusMask(mask) => vec![0x02, *mask],
+            DTCReport::Snapshot { dtc, record } => {
+                [&[0x04], &dtc_bytes(dtc)[..], &[*record]].concat()
+            }
+            DTCReport::ExtendedData { dtc, record } => {
+                [&[0x06], &dtc_bytes(dtc)[..], &[*record]].concat()
+            }
+            DTCReport::Supported => vec![0x0a],
+        }
+    }
+}
+
+impl IoControlType {
+    /// InputOutputControlParameter of the request.
+    pub fn id(&self) -> u8 {
+        match self {
+            IoControlType::ReturnControlToEcu => 0x00,
+            IoControlType::ResetToDefault => 0x01,
+            IoControlType::FreezeCurrentState => 0x02,
+            IoControlType::ShortTermAdjustment => 0x03,
+        }
+    }
+}
+
+impl RoutineControlType {
+    /// Subfunction of the RoutineControl request.
+    pub fn id(&self) -> u8 {
+        match self {
+            RoutineControlType::Start => 0x01,
+            RoutineControlType::Stop => 0x02,
+            RoutineControlType::RequestResults => 0x03,
+        }
+    }
+}
+
+impl ResetType {
+    /// Subfunction of the ECUReset request.
+    pub fn id(&self) -> u8 {
+        match self {
+            ResetType::Hard => 0x01,
+            ResetType::KeyOffOn => 0x02,
+            ResetType::Soft => 0x03,
+            ResetType::Oem(id) => *id,
+        }
+    }
+}
+
+impl CommunicationControlType {
+    /// Subfunction of the CommunicationControl request.
+    pub fn id(&self) -> u8 {
+        match self {
+            CommunicationControlType::EnableRxAndTx => 0x00,
+            CommunicationControlType::EnableRxAndDisableTx => 0x01,
+            CommunicationControlType::DisableRxAndEnableTx => 0x02,
+            CommunicationControlType::DisableRxAndTx => 0x03,
+        }
+    }
+}
+
+impl CommunicationType {
+    /// Communication type bits of the communicationType parameter.
+    pub fn id(&self) -> u8 {
+        match self {
+            CommunicationType::Normal => 0x01,
+            CommunicationType::NetworkManagement => 0x02,
+            CommunicationType::NormalAndNetworkManagement => 0x03,
+        }
+    }
 }
 
-pub fn read_scenario(filename: &str) -> Steps {
-    configfile::read_file(filename)
+impl DTCSetting {
+    /// Subfunction of the ControlDTCSetting request.
+    pub fn id(&self) -> u8 {
+        match self {
+            DTCSetting::On => 0x01,
+            DTCSetting::Off => 0x02,
+        }
+    }
+}
+
+impl ParamValue {
+    /// Parse a value as an integer, a list of bytes such as "[0x01, 0x02]", or
+    /// a string otherwise. A value between brackets must be a list of bytes.
+    pub fn parse(s: &str) -> Result<ParamValue, String> {
+        if s.trim_start().starts_with('[') {
+            return serde_yaml::from_str(s)
+                .map(ParamValue::Bytes)
+                .map_err(|err| format!("\"{s}\" is not a list of bytes: {err}"));
+        }
+        Ok(serde_yaml::from_str(s).unwrap_or_else(|_| ParamValue::String(s.to_string())))
+    }
+}
+
+impl Scenario {
+    /// Append the steps, parameters and procedures of another scenario.
+    pub fn append(&mut self, other: Scenario) -> Result<(), ScenarioError> {
+        for (name, default) in other.parameters.into_iter() {
+            add_parameter(&mut self.parameters, name, default);
+        }
+        for (name, procedure) in other.procedures.into_iter() {
+            add_procedure(&mut self.procedures, name, procedure)?;
+        }
+        self.steps.extend(other.steps);
+        Ok(())
+    }
+
+    /// Compute the values of all parameters from the given values and the
+    /// declared default values.
+    ///
+    /// Given values of undeclared parameters are kept, and a declared parameter
+    /// without a given value nor a default value is an error.
+    pub fn bind_parameters(
+        &self,
+        given: &BTreeMap<String, ParamValue>,
+    ) -> Result<BTreeMap<String, ParamValue>, ScenarioError> {
+        let mut values = given.clone();
+        for (name, default) in self.parameters.iter() {
+            if !values.contains_key(name) {
+                let value = default
+                    .clone()
+                    .ok_or_else(|| ScenarioError::MissingParameter(name.clone()))?;
+                values.insert(name.clone(), value);
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn add_parameter(parameters: &mut Parameters, name: String, default: Option<ParamValue>) {
+    let existing = parameters.entry(name).or_default();
+    if existing.is_none() {
+        *existing = default;
+    }
+}
+
+fn add_procedure(
+    procedures: &mut Procedures,
+    name: String,
+    procedure: Procedure,
+) -> Result<(), ScenarioError> {
+    match procedures.get(&name) {
+        Some(existing) if *existing != procedure => Err(ScenarioError::DuplicateProcedure(name)),
+        Some(_) => Ok(()),
+        None => {
+            procedures.insert(name, procedure);
+            Ok(())
+        }
+    }
+}
+
+pub fn read_scenario(filename: &str) -> Result<Scenario, ScenarioError> {
+    let path = include::canonicalize(Path::new(filename))?;
+    let mut scenario = configfile::read_file(&path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    include::resolve(&mut scenario, dir, vec![path.clone()])?;
+    Ok(scenario)
 }
 
 #[allow(dead_code)]
-pub fn read_scenario_str(s: &str) -> Steps {
-    configfile::read_str(s)
+pub fn read_scenario_str(s: &str) -> Result<Scenario, ScenarioError> {
+    let mut scenario = configfile::read_str(s)?;
+    include::resolve(&mut scenario, Path::new("."), vec![])?;
+    Ok(scenario)
 }
 
 mod configfile {
-    use super::Steps;
-    use serde_yaml::from_reader;
-    use std::io;
+    use super::{Scenario, ScenarioError};
+    use std::path::Path;
+
+    pub(super) fn read_file(filename: &Path) -> Result<Scenario, ScenarioError> {
+        let s = std::fs::read_to_string(filename)
+            .map_err(|err| ScenarioError::ScenarioFile(filename.display().to_string(), err))?;
+        from_str(&s).map_err(|err| ScenarioError::Parse(filename.display().to_string(), err))
+    }
+
+    pub(super) fn read_str(s: &str) -> Result<Scenario, ScenarioError> {
+        from_str(s).map_err(|err| ScenarioError::Parse("<str>".to_string(), err))
+    }
+
+    /// Parse either a scenario document, or a plain list of steps.
+    fn from_str(s: &str) -> Result<Scenario, serde_yaml::Error> {
+        let value: serde_yaml::Value = serde_yaml::from_str(s)?;
+        if value.is_mapping() {
+            serde_yaml::from_str(s)
+        } else {
+            Ok(Scenario {
+                steps: serde_yaml::from_str(s)?,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+mod include {
+    use super::{
+        add_parameter, add_procedure, configfile, Parameters, Procedures, Scenario, ScenarioError,
+        Step, Steps,
+    };
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    pub(super) fn canonicalize(filename: &Path) -> Result<PathBuf, ScenarioError> {
+        filename
+            .canonicalize()
+            .map_err(|err| ScenarioError::ScenarioFile(filename.display().to_string(), err))
+    }
+
+    /// Load the steps of all Include steps of a scenario, recursively.
+    ///
+    /// Included filenames are relative to the directory `dir` of the including
+    /// file, and `stack` holds the chain of files being included, to detect
+    /// cycles. The procedures of the included files are gathered into the
+    /// procedures of the scenario.
+    ///
+    /// Every Break and Continue must be inside a loop of the same scenario or
+    /// procedure, a loop control not leaving an Include or a Call. Every Call
+    /// must name a procedure, with its parameters as arguments.
+    pub(super) fn resolve(
+        scenario: &mut Scenario,
+        dir: &Path,
+        stack: Vec<PathBuf>,
+    ) -> Result<(), ScenarioError> {
+        let mut resolver = Resolver {
+            stack,
+            parameters: Parameters::new(),
+            procedures: Procedures::new(),
+        };
+        resolver.resolve_scenario(scenario, dir)?;
+        scenario.parameters = resolver.parameters;
+        scenario.procedures = resolver.procedures;
+
+        let params: BTreeMap<String, Vec<String>> = scenario
+            .procedures
+            .iter()
+            .map(|(name, procedure)| (name.clone(), procedure.params.clone()))
+            .collect();
+        check_calls(&mut scenario.steps, &params)?;
+        for procedure in scenario.procedures.values_mut() {
+            check_calls(&mut procedure.steps, &params)?;
+        }
+        Ok(())
+    }
+
+    fn check_calls(
+        steps: &mut Steps,
+        params: &BTreeMap<String, Vec<String>>,
+    ) -> Result<(), ScenarioError> {
+        for step in steps.iter_mut() {
+            if let Step::Call(call) = step {
+                let procedure_params = params.get(&call.procedure).ok_or_else(|| {
+                    ScenarioError::InvalidCall(
+                        call.procedure.clone(),
+                        "unknown procedure".to_string(),
+                    )
+                })?;
+                call.check_args(procedure_params)?;
+            }
+            for substeps in step.substeps_mut() {
+                check_calls(substeps, params)?;
+            }
+        }
+        Ok(())
+    }
+
+    struct Resolver {
+        stack: Vec<PathBuf>,
+        parameters: Parameters,
+        procedures: Procedures,
+    }
+
+    impl Resolver {
+        fn resolve_scenario(
+            &mut self,
+            scenario: &mut Scenario,
+            dir: &Path,
+        ) -> Result<(), ScenarioError> {
+            self.resolve_steps(&mut scenario.steps, dir, false)?;
+            for (name, default) in std::mem::take(&mut scenario.parameters) {
+                add_parameter(&mut self.parameters, name, default);
+            }
+            for (name, mut procedure) in std::mem::take(&mut scenario.procedures) {
+                self.resolve_steps(&mut procedure.steps, dir, false)?;
+                add_procedure(&mut self.procedures, name, procedure)?;
+            }
+            Ok(())
+        }
+
+        fn resolve_steps(
+            &mut self,
+            steps: &mut Steps,
+            dir: &Path,
+            in_loop: bool,
+        ) -> Result<(), ScenarioError> {
+            for step in steps.iter_mut() {
+                if matches!(step, Step::Break(_) | Step::Continue(_)) && !in_loop {
+                    return Err(ScenarioError::InvalidStep(
+                        step.name().to_string(),
+                        "outside of a loop".to_string(),
+                    ));
+                }
+                if let Step::Include(inc) = step {
+                    let path = canonicalize(&dir.join(&inc.filename))?;
+                    if self.stack.contains(&path) {
+                        let chain = self
+                            .stack
+                            .iter()
+                            .chain(std::iter::once(&path))
+                            .map(|p| p.display().to_string())
+                            .collect::<Vec<String>>()
+                            .join(" -> ");
+                        return Err(ScenarioError::IncludeCycle(chain));
+                    }
+                    let mut included = configfile::read_file(&path)?;
+                    let dir = path.parent().unwrap_or(Path::new("."));
+                    self.stack.push(path.clone());
+                    self.resolve_scenario(&mut included, dir)?;
+                    self.stack.pop();
+                    inc.steps = included.steps;
+                } else {
+                    let in_loop = in_loop
+                        || matches!(
+                            step,
+                            Step::ForEach(_) | Step::Repeat(_) | Step::WhileLoop(_)
+                        );
+                    for substeps in step.substeps_mut() {
+                        self.resolve_steps(substeps, dir, in_loop)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+mod evaluable {
+    use super::{evalexpression::Expression, Evaluable};
+    use serde::de::{self, EnumAccess, IntoDeserializer, VariantAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::{fmt, marker::PhantomData};
+
+    impl<T: Serialize> Serialize for Evaluable<T> {
+        fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self {
+                Evaluable::Value(value) => value.serialize(s),
+                Evaluable::Expr(expr) => {
+                    s.serialize_newtype_variant("Evaluable", 1, "Expr", &expr.str)
+                }
+            }
+        }
+    }
+
+    struct EvaluableVisitor<T> {
+        /// A string which is not a valid value is an expression, where no
+        /// `!Expr` tag can be added
+        untagged_expr: bool,
+        marker: PhantomData<T>,
+    }
+
+    fn parse_expr<T, E: de::Error>(s: &str) -> Result<Evaluable<T>, E> {
+        Expression::try_from(s)
+            .map(Evaluable::Expr)
+            .map_err(|err| E::custom(format!("Cannot parse evalexpr: \"{s}\": {err}")))
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for EvaluableVisitor<T> {
+        type Value = Evaluable<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a value, or an !Expr evalexpr expression")
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(Evaluable::Value)
+        }
 
-    pub(super) fn read_file(filename: &str) -> Steps {
-        let f = std::fs::File::open(filename)
-            .inspect_err(|err| println!("Can't open configuration file {filename}: {err}"))
-            .unwrap();
-        let steps: Steps = from_reader(f).unwrap();
-        steps
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(Evaluable::Value)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            let deserializer: de::value::StrDeserializer<de::value::Error> = v.into_deserializer();
+            match T::deserialize(deserializer) {
+                Ok(value) => Ok(Evaluable::Value(value)),
+                Err(_) if self.untagged_expr => parse_expr(v),
+                Err(err) => Err(E::custom(format!(
+                    "{err}, an expression is written !Expr {v}"
+                ))),
+            }
+        }
+
+        fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+        where
+            A: EnumAccess<'de>,
+        {
+            let (tag, variant): (String, _) = data.variant()?;
+            if tag != "Expr" {
+                return Err(de::Error::unknown_variant(&tag, &["Expr"]));
+            }
+            let s: String = variant.newtype_variant()?;
+            parse_expr(&s)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for Evaluable<T> {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(EvaluableVisitor {
+                untagged_expr: false,
+                marker: PhantomData,
+            })
+        }
     }
 
-    pub(super) fn read_str(s: &str) -> Steps {
-        let steps: Steps = from_reader(io::Cursor::new(s)).unwrap();
-        steps
+    /// Deserialize a value, or an expression with or without the `!Expr` tag,
+    /// such as `!SleepMs delay * 2`.
+    pub fn untagged<'de, D, T>(deserializer: D) -> Result<Evaluable<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        deserializer.deserialize_any(EvaluableVisitor {
+            untagged_expr: true,
+            marker: PhantomData,
+        })
     }
 }
 
@@ -198,6 +1291,86 @@ impl RawBytes {
     }
 }
 
+/// DTC number, of 3 bytes in the requests.
+mod dtc_number {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(dtc: &u32, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_u32(*dtc)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<u32, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let dtc = u32::deserialize(deserializer)?;
+        if dtc > 0xff_ffff {
+            return Err(serde::de::Error::custom(format!(
+                "DTC {dtc:#x} is longer than 3 bytes"
+            )));
+        }
+        Ok(dtc)
+    }
+}
+
+mod replyregex {
+    use regex::Regex;
+
+    /// Regex of an ExpectReply, compiled when the scenario is loaded, the
+    /// spaces being ignored.
+    #[derive(Debug)]
+    pub struct ReplyRegex {
+        pub str: String,
+        pub compiled: Regex,
+    }
+
+    impl PartialEq for ReplyRegex {
+        fn eq(&self, other: &Self) -> bool {
+            self.str == other.str
+        }
+    }
+
+    impl TryFrom<&str> for ReplyRegex {
+        type Error = String;
+
+        fn try_from(s: &str) -> Result<Self, Self::Error> {
+            let compiled = Regex::new(&s.replace(' ', ""))
+                .map_err(|err| format!("Invalid regex \"{s}\": {err}"))?;
+            Ok(ReplyRegex {
+                str: s.to_owned(),
+                compiled,
+            })
+        }
+    }
+
+    pub mod option {
+        use super::ReplyRegex;
+        use serde::{self, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(regex: &Option<ReplyRegex>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match regex {
+                Some(regex) => s.serialize_some(&regex.str),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<ReplyRegex>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s: Option<String> = Option::deserialize(deserializer)?;
+            s.map(|s| ReplyRegex::try_from(s.as_str()).map_err(serde::de::Error::custom))
+                .transpose()
+        }
+    }
+}
+
 mod evalexpression {
     use evalexpr;
     use serde::{self, Deserialize, Deserializer, Serializer};
@@ -237,6 +1410,69 @@ mod evalexpression {
             serde::de::Error::custom(format!("Cannot parse evalexpr: \"{s}\": {err}"))
         })
     }
+
+    /// Optional expression, such as the condition of a Break.
+    pub mod option {
+        use super::Expression;
+        use serde::{self, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(expr: &Option<Expression>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match expr {
+                Some(expr) => s.serialize_some(&expr.str),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Expression>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s: Option<String> = Option::deserialize(deserializer)?;
+            s.map(|s| {
+                Expression::try_from(s.as_str()).map_err(|err| {
+                    serde::de::Error::custom(format!("Cannot parse evalexpr: \"{s}\": {err}"))
+                })
+            })
+            .transpose()
+        }
+    }
+
+    /// Named expressions, such as the arguments of an Include.
+    pub mod map {
+        use super::Expression;
+        use serde::{self, Deserialize, Deserializer, Serializer};
+        use std::collections::BTreeMap;
+
+        pub fn serialize<S>(map: &BTreeMap<String, Expression>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            s.collect_map(map.iter().map(|(name, expr)| (name, &expr.str)))
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<BTreeMap<String, Expression>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let strs: BTreeMap<String, String> = BTreeMap::deserialize(deserializer)?;
+            strs.into_iter()
+                .map(|(name, s)| {
+                    Expression::try_from(s.as_str())
+                        .map(|expr| (name, expr))
+                        .map_err(|err| {
+                            serde::de::Error::custom(format!(
+                                "Cannot parse evalexpr: \"{s}\": {err}"
+                            ))
+                        })
+                })
+                .collect()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -247,37 +1483,72 @@ mod test {
     #[test]
     fn sample_config_file() {
         use std::io;
-        let step1 = Step::ReadSupportedDTC(ReadSupportedDTC {});
+        let step1 = Step::ReadSupportedDTC(ReadSupportedDTC {
+            options: StepOptions::default(),
+        });
         let step2 = Step::TransferDownload(TransferDownload {
             compression_method: 1,
             encrypt_method: 0,
-            addr: 0xfd01,
-            memorysize: 4,
-            filename: "FD01.bin".to_string(),
+            addr: Evaluable::Value(0xfd01),
+            memorysize: Evaluable::Value(4),
+            filename: Evaluable::Value("FD01.bin".to_string()),
+            options: StepOptions::default(),
+        });
+        let step3 = Step::ReadDID(ReadDID {
+            did: Evaluable::Value(0xf190),
+            options: StepOptions::default(),
+        });
+        let step4 = Step::AbortIfNrc(AbortIfNrc {
+            nrc: Some(0x22),
+            options: StepOptions::default(),
         });
-        let step3 = Step::ReadDID(ReadDID { did: 0xf190 });
-        let step4 = Step::AbortIfNrc(AbortIfNrc { nrc: Some(0x22) });
         /*
         let step5 = Step::RawUds(RawUds {
                 uds_bytes: vec![0x22, 0xfd, 0x01],
         });
         */
-        let step6 = Step::AbortIfNrc(AbortIfNrc { nrc: None });
+        let step6 = Step::AbortIfNrc(AbortIfNrc {
+            nrc: None,
+            options: StepOptions::default(),
+        });
         let step7 = Step::EvalExpr(EvalExpr {
             expression: evalexpression::Expression::try_from("a = 1;").unwrap(),
+            options: StepOptions::default(),
         });
         let step8 = Step::WhileLoop(WhileLoop {
             condition: evalexpression::Expression::try_from("a < 3;").unwrap(),
             steps: vec![
-                Step::ReadDID(ReadDID { did: 0xf190 }),
-                Step::ReadDID(ReadDID { did: 0xf191 }),
+                Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf190),
+                    options: StepOptions::default(),
+                }),
+                Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf191),
+                    options: StepOptions::default(),
+                }),
                 Step::EvalExpr(EvalExpr {
                     expression: evalexpression::Expression::try_from("a = a + 1;").unwrap(),
+                    options: StepOptions::default(),
                 }),
             ],
+            options: StepOptions::default(),
         });
 
         let scenario = Scenario {
+            parameters: Parameters::from([
+                ("vin".to_string(), None),
+                ("did".to_string(), Some(ParamValue::Int(0xf190))),
+            ]),
+            procedures: Procedures::from([(
+                "read_vin".to_string(),
+                Procedure {
+                    params: vec![],
+                    steps: vec![Step::ReadDID(ReadDID {
+                        did: Evaluable::Value(0xf190),
+                        options: StepOptions::default(),
+                    })],
+                },
+            )]),
             steps: vec![step1, step2, step3, step4, step6, step7, step8],
         };
         to_writer(&io::stdout(), &scenario).unwrap();
@@ -288,7 +1559,10 @@ mod test {
         use std::io;
 
         let steps = generate_all_possible_steps();
-        let scenario1 = Scenario { steps };
+        let scenario1 = Scenario {
+            steps,
+            ..Default::default()
+        };
         to_writer(&io::stdout(), &scenario1).unwrap();
 
         // Check bijection of yaml and deserialized structure
@@ -299,72 +1573,494 @@ mod test {
         assert_eq!(&scenario1.steps, &scenario2.steps);
     }
 
+    #[test]
+    fn step_options() {
+        let s = "- !ReadDID\n  did: !Expr base + 1\n  timeout_ms: 100\n";
+        let scenario = read_scenario_str(s).unwrap();
+        let Step::ReadDID(rd) = &scenario.steps[0] else {
+            panic!("unexpected step {:?}", scenario.steps[0]);
+        };
+        assert!(matches!(rd.did, Evaluable::Expr(_)));
+        assert_eq!(scenario.steps[0].timeout_ms(), Some(100));
+    }
+
+    #[test]
+    fn param_value_parse() {
+        assert_eq!(
+            ParamValue::parse("0xf190").unwrap(),
+            ParamValue::Int(0xf190)
+        );
+        assert_eq!(ParamValue::parse("-12").unwrap(), ParamValue::Int(-12));
+        assert_eq!(
+            ParamValue::parse("[0x01, 2]").unwrap(),
+            ParamValue::Bytes(vec![0x01, 0x02])
+        );
+        assert_eq!(
+            ParamValue::parse("VF1R").unwrap(),
+            ParamValue::String("VF1R".to_string())
+        );
+        assert_eq!(
+            ParamValue::parse("").unwrap(),
+            ParamValue::String("".to_string())
+        );
+        assert!(ParamValue::parse("[300]").is_err());
+        assert!(ParamValue::parse("[0x01, VF1R]").is_err());
+    }
+
     fn generate_all_possible_steps() -> Steps {
         vec![
-            Step::AbortIfNrc(AbortIfNrc { nrc: Some(0x10) }),
-            Step::AbortIfNrc(AbortIfNrc { nrc: None }),
+            Step::AbortIfNrc(AbortIfNrc {
+                nrc: Some(0x10),
+                options: StepOptions::default(),
+            }),
+            Step::AbortIfNrc(AbortIfNrc {
+                nrc: None,
+                options: StepOptions::default(),
+            }),
+            Step::Break(Break {
+                condition: Some("i == 3".try_into().unwrap()),
+                options: StepOptions::default(),
+            }),
+            Step::Break(Break {
+                condition: None,
+                options: StepOptions::default(),
+            }),
+            Step::Call(Call {
+                procedure: "read_vin".to_string(),
+                args: BTreeMap::new(),
+                local: false,
+                options: StepOptions::default(),
+            }),
+            Step::Call(Call {
+                procedure: "unlock_level".to_string(),
+                args: BTreeMap::from([("level".to_string(), "1".try_into().unwrap())]),
+                local: true,
+                options: StepOptions::default(),
+            }),
+            Step::ClearDTC(ClearDTC {
+                group: None,
+                options: StepOptions::default(),
+            }),
+            Step::ClearDTC(ClearDTC {
+                group: Some(0x000100),
+                options: StepOptions::default(),
+            }),
+            Step::CommunicationControl(CommunicationControl {
+                control: CommunicationControlType::DisableRxAndTx,
+                communication: Some(CommunicationType::NormalAndNetworkManagement),
+                subnet: None,
+                suppress_response: false,
+                options: StepOptions::default(),
+            }),
+            Step::Continue(Continue {
+                condition: Some("reply_nth(0) == 0x7f".try_into().unwrap()),
+                options: StepOptions::default(),
+            }),
+            Step::ControlDTCSetting(ControlDTCSetting {
+                setting: DTCSetting::Off,
+                suppress_response: true,
+                options: StepOptions::default(),
+            }),
+            Step::DiagnosticSession(DiagnosticSession {
+                session: Session::Extended,
+                suppress_response: false,
+                options: StepOptions::default(),
+            }),
+            Step::DiagnosticSession(DiagnosticSession {
+                session: Session::Oem(0x40),
+                suppress_response: true,
+                options: StepOptions::default(),
+            }),
             Step::DisconnectDoIp(DisconnectDoIp {
                 wait_after_ms: Some(1000),
+                options: StepOptions::default(),
             }),
             Step::DisconnectDoIp(DisconnectDoIp {
                 wait_after_ms: None,
+                options: StepOptions::default(),
+            }),
+            Step::EcuReset(EcuReset {
+                reset_type: ResetType::Hard,
+                suppress_response: false,
+                max_wait_ms: Some(30000),
+                reset_delay_ms: Some(1000),
+                wait_announcement: false,
+                options: StepOptions::default(),
+            }),
+            Step::EcuReset(EcuReset {
+                reset_type: ResetType::Oem(0x40),
+                suppress_response: true,
+                max_wait_ms: None,
+                reset_delay_ms: None,
+                wait_announcement: true,
+                options: StepOptions::default(),
             }),
             Step::EvalExpr(EvalExpr {
                 expression: "a = a + 1;".try_into().unwrap(),
+                options: StepOptions::default(),
             }),
             Step::EvalExpr(EvalExpr {
                 expression: "print(reply)".try_into().unwrap(),
+                options: StepOptions::default(),
             }),
             Step::EvalExpr(EvalExpr {
                 expression: "print(reply_nth(0))".try_into().unwrap(),
+                options: StepOptions::default(),
             }),
             Step::EvalExpr(EvalExpr {
                 expression: "vin = loadfile(\"vin.bin\"); print(vin);"
                     .try_into()
                     .unwrap(),
+                options: StepOptions::default(),
             }),
             Step::EvalExpr(EvalExpr {
                 expression: "reply_nth(0) == 0x62".try_into().unwrap(),
+                options: StepOptions::default(),
+            }),
+            Step::ExpectReply(ExpectReply {
+                starts_with: Some(RawBytes::Bytes(vec![0x62, 0xf1, 0x90])),
+                bytes: Some(vec![
+                    ExpectByte {
+                        index: 3,
+                        value: 0x01,
+                        mask: Some(0x0f),
+                    },
+                    ExpectByte {
+                        index: 4,
+                        value: 0x46,
+                        mask: None,
+                    },
+                ]),
+                regex: None,
+                on_failure: None,
+                options: StepOptions::default(),
+            }),
+            Step::ExpectReply(ExpectReply {
+                starts_with: None,
+                bytes: None,
+                regex: Some("62 f1 90 (.. )*56".try_into().unwrap()),
+                on_failure: Some(OnFailure::Continue),
+                options: StepOptions::default(),
+            }),
+            Step::ForEach(ForEach {
+                variable: "did".to_string(),
+                items: ForEachItems::Tuple("(0xf190, 0xf191)".try_into().unwrap()),
+                steps: vec![Step::EvalExpr(EvalExpr {
+                    expression: "print(did)".try_into().unwrap(),
+                    options: StepOptions::default(),
+                })],
+                options: StepOptions::default(),
+            }),
+            Step::ForEach(ForEach {
+                variable: "i".to_string(),
+                items: ForEachItems::Range(Range {
+                    start: 0,
+                    end: 10,
+                    step: NonZeroI64::new(2),
+                }),
+                steps: vec![Step::EvalExpr(EvalExpr {
+                    expression: "print(i)".try_into().unwrap(),
+                    options: StepOptions::default(),
+                })],
+                options: StepOptions::default(),
+            }),
+            Step::If(If {
+                condition: evalexpression::Expression::try_from("reply_nth(0) == 0x62").unwrap(),
+                then: vec![Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf190),
+                    options: StepOptions::default(),
+                })],
+                otherwise: Some(vec![Step::PrintLastReply]),
+                options: StepOptions::default(),
+            }),
+            Step::If(If {
+                condition: evalexpression::Expression::try_from("a < 3").unwrap(),
+                then: vec![Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf190),
+                    options: StepOptions::default(),
+                })],
+                otherwise: None,
+                options: StepOptions::default(),
+            }),
+            Step::Include(Include {
+                filename: "ReadDID.yaml".to_string(),
+                args: BTreeMap::new(),
+                local: false,
+                steps: vec![],
+                options: StepOptions::default(),
+            }),
+            Step::Include(Include {
+                filename: "unlock.yaml".to_string(),
+                args: BTreeMap::from([
+                    ("level".to_string(), "1".try_into().unwrap()),
+                    ("key".to_string(), "(0x01, 0x02)".try_into().unwrap()),
+                ]),
+                local: true,
+                steps: vec![],
+                options: StepOptions::default(),
+            }),
+            Step::IoControl(IoControl {
+                did: Evaluable::Value(0xf010),
+                control: IoControlType::ShortTermAdjustment,
+                state: Some(RawBytes::Bytes(vec![0x01])),
+                mask: Some(RawBytes::Bytes(vec![0xff])),
+                options: StepOptions::default(),
+            }),
+            Step::IoControl(IoControl {
+                did: Evaluable::Value(0xf010),
+                control: IoControlType::ReturnControlToEcu,
+                state: None,
+                mask: None,
+                options: StepOptions::default(),
             }),
             Step::PrintLastReply,
             Step::RawUds(RawUds {
                 data: RawBytes::BinFileName("raw_file.bin".to_string()),
+                options: StepOptions::default(),
             }),
             Step::RawUds(RawUds {
                 data: RawBytes::EvalExprVarname("request".to_string()),
+                options: StepOptions::default(),
             }),
             Step::RawUds(RawUds {
                 data: RawBytes::Bytes(vec![0x22, 0xf1, 0x90]),
+                options: StepOptions::default(),
+            }),
+            Step::ReadDID(ReadDID {
+                did: Evaluable::Value(0xf190),
+                options: StepOptions::default(),
+            }),
+            Step::ReadDID(ReadDID {
+                did: Evaluable::Value(0xf190),
+                options: StepOptions {
+                    timeout_ms: Some(500),
+                },
+            }),
+            Step::ReadDID(ReadDID {
+                did: Evaluable::Expr("base_did + i".try_into().unwrap()),
+                options: StepOptions::default(),
+            }),
+            Step::ReadDTC(ReadDTC {
+                report: DTCReport::NumberByStatusMask(0x08),
+                options: StepOptions::default(),
+            }),
+            Step::ReadDTC(ReadDTC {
+                report: DTCReport::ByStatusMask(0x08),
+                options: StepOptions::default(),
+            }),
+            Step::ReadDTC(ReadDTC {
+                report: DTCReport::Snapshot {
+                    dtc: 0x012345,
+                    record: 0xff,
+                },
+                options: StepOptions::default(),
+            }),
+            Step::ReadDTC(ReadDTC {
+                report: DTCReport::ExtendedData {
+                    dtc: 0x012345,
+                    record: 0x01,
+                },
+                options: StepOptions::default(),
+            }),
+            Step::ReadDTC(ReadDTC {
+                report: DTCReport::Supported,
+                options: StepOptions::default(),
+            }),
+            Step::ReadMemoryByAddress(ReadMemoryByAddress {
+                addr: Evaluable::Value(0x4000),
+                size: Evaluable::Value(1024),
+                address_bytes: Some(4),
+                size_bytes: Some(2),
+                chunk_size: Some(128),
+                filename: Some(Evaluable::Value("memory.bin".to_string())),
+                variable: None,
+                options: StepOptions::default(),
+            }),
+            Step::ReadSupportedDTC(ReadSupportedDTC {
+                options: StepOptions::default(),
+            }),
+            Step::Repeat(Repeat {
+                count: 3,
+                variable: Some("i".to_string()),
+                steps: vec![Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf190),
+                    options: StepOptions::default(),
+                })],
+                options: StepOptions::default(),
+            }),
+            Step::Retry(Retry {
+                max_attempts: NonZeroUsize::new(3).unwrap(),
+                delay_ms: 100,
+                backoff_factor: Some(2),
+                nrcs: Some(vec![0x21, 0x22]),
+                errors: Some(vec!["Nrc".to_string(), "NetworkConnectorDead".to_string()]),
+                steps: vec![Step::RawUds(RawUds {
+                    data: RawBytes::Bytes(vec![0x10, 0x03]),
+                    options: StepOptions::default(),
+                })],
+                options: StepOptions::default(),
+            }),
+            Step::Retry(Retry {
+                max_attempts: NonZeroUsize::new(5).unwrap(),
+                delay_ms: 1000,
+                backoff_factor: None,
+                nrcs: None,
+                errors: None,
+                steps: vec![Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf190),
+                    options: StepOptions::default(),
+                })],
+                options: StepOptions::default(),
+            }),
+            Step::RoutineControl(RoutineControl {
+                control: RoutineControlType::Start,
+                routine: Evaluable::Value(0xff00),
+                option: Some(RawBytes::Bytes(vec![0x44, 0x00, 0x00, 0x40, 0x00])),
+                suppress_response: false,
+                poll_until: Some("reply_nth(4) == 0".try_into().unwrap()),
+                poll_interval_ms: Some(100),
+                poll_timeout_ms: Some(60000),
+                options: StepOptions::default(),
+            }),
+            Step::RoutineControl(RoutineControl {
+                control: RoutineControlType::RequestResults,
+                routine: Evaluable::Value(0xff01),
+                option: None,
+                suppress_response: true,
+                poll_until: None,
+                poll_interval_ms: None,
+                poll_timeout_ms: None,
+                options: StepOptions::default(),
+            }),
+            Step::SecurityAccess(SecurityAccess {
+                level: 0x01,
+                key: KeySource::Command(KeyCommand {
+                    program: "./seedkey.sh".to_string(),
+                    args: vec![],
+                    seed: SeedInput::Stdin,
+                }),
+                max_attempts: None,
+                delay_ms: None,
+                options: StepOptions::default(),
+            }),
+            Step::SecurityAccess(SecurityAccess {
+                level: 0x03,
+                key: KeySource::Command(KeyCommand {
+                    program: "seedkey".to_string(),
+                    args: vec!["--level".to_string(), "3".to_string()],
+                    seed: SeedInput::Argument,
+                }),
+                max_attempts: Some(3),
+                delay_ms: Some(10000),
+                options: StepOptions::default(),
+            }),
+            Step::SecurityAccess(SecurityAccess {
+                level: 0x11,
+                key: KeySource::Plugin(KeyPlugin {
+                    variant: "ECU1".to_string(),
+                    key_length: Some(4),
+                }),
+                max_attempts: None,
+                delay_ms: None,
+                options: StepOptions::default(),
+            }),
+            Step::SleepMs(Evaluable::Value(1000)),
+            Step::TesterPresent(TesterPresent {
+                period_ms: Some(2000),
+                stop: false,
+                options: StepOptions::default(),
+            }),
+            Step::TesterPresent(TesterPresent {
+                period_ms: None,
+                stop: true,
+                options: StepOptions::default(),
+            }),
+            Step::Try(Try {
+                steps: vec![Step::TransferDownload(TransferDownload {
+                    compression_method: 0x01,
+                    encrypt_method: 0x0,
+                    addr: Evaluable::Value(0x4000),
+                    filename: Evaluable::Value("FD01.bin".to_string()),
+                    memorysize: Evaluable::Value(10240),
+                    options: StepOptions::default(),
+                })],
+                on_error: Some(vec![
+                    Step::EvalExpr(EvalExpr {
+                        expression: "print(error_message)".try_into().unwrap(),
+                        options: StepOptions::default(),
+                    }),
+                    Step::RawUds(RawUds {
+                        data: RawBytes::Bytes(vec![0x10, 0x01]),
+                        options: StepOptions::default(),
+                    }),
+                ]),
+                finally: Some(vec![Step::PrintLastReply]),
+                options: StepOptions::default(),
+            }),
+            Step::Try(Try {
+                steps: vec![Step::ReadDID(ReadDID {
+                    did: Evaluable::Value(0xf190),
+                    options: StepOptions::default(),
+                })],
+                on_error: None,
+                finally: Some(vec![Step::PrintLastReply]),
+                options: StepOptions::default(),
             }),
-            Step::ReadDID(ReadDID { did: 0xf190 }),
-            Step::SleepMs(1000),
             Step::WhileLoop(WhileLoop {
                 condition: evalexpression::Expression::try_from("a < 3").unwrap(),
                 steps: vec![
-                    Step::ReadDID(ReadDID { did: 0xf190 }),
+                    Step::ReadDID(ReadDID {
+                        did: Evaluable::Value(0xf190),
+                        options: StepOptions::default(),
+                    }),
                     Step::EvalExpr(EvalExpr {
                         expression: evalexpression::Expression::try_from("a = a + 1;").unwrap(),
+                        options: StepOptions::default(),
                     }),
                 ],
+                options: StepOptions::default(),
             }),
             Step::WriteDID(WriteDID {
-                did: 0xf190,
+                did: Evaluable::Value(0xf190),
                 data: RawBytes::Bytes("VF1FRSYSBENCH01".as_bytes().to_vec()),
+                options: StepOptions::default(),
             }),
             Step::WriteDID(WriteDID {
-                did: 0xf190,
+                did: Evaluable::Value(0xf190),
                 data: RawBytes::BinFileName("toto.bin".to_string()),
+                options: StepOptions::default(),
             }),
             Step::WriteDID(WriteDID {
-                did: 0xf190,
+                did: Evaluable::Value(0xf190),
                 data: RawBytes::EvalExprVarname("vin".to_string()),
+                options: StepOptions::default(),
+            }),
+            Step::WriteMemoryByAddress(WriteMemoryByAddress {
+                addr: Evaluable::Value(0x4000),
+                data: RawBytes::Bytes(vec![0x01, 0x02, 0x03]),
+                address_bytes: None,
+                size_bytes: None,
+                chunk_size: None,
+                options: StepOptions::default(),
             }),
             Step::TransferDownload(TransferDownload {
                 compression_method: 0x01,
                 encrypt_method: 0x0,
-                addr: 0x4000,
-                filename: "FD01.bin".to_string(),
-                memorysize: 10240,
+                addr: Evaluable::Value(0x4000),
+                filename: Evaluable::Value("FD01.bin".to_string()),
+                memorysize: Evaluable::Value(10240),
+                options: StepOptions::default(),
+            }),
+            Step::TransferUpload(TransferUpload {
+                compression_method: 0x00,
+                encrypt_method: 0x00,
+                addr: Evaluable::Value(0x4000),
+                memorysize: Evaluable::Value(1024),
+                address_bytes: None,
+                size_bytes: None,
+                filename: Some(Evaluable::Value("dump.bin".to_string())),
+                variable: Some("dump".to_string()),
+                options: StepOptions::default(),
             }),
         ]
     }
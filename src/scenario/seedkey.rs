@@ -0,0 +1,188 @@
+use libloading::{Library, Symbol};
+use std::ffi::{c_char, c_int, c_uint, CString};
+use std::io;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::error::ScenarioError;
+use super::parser::{KeyCommand, KeyPlugin, KeySource, SeedInput};
+
+/// Seed-key entry point of the libraries, returning 0 on success.
+type GenerateKeyEx = unsafe extern "C" fn(
+    seed: *const u8,
+    seed_size: c_uint,
+    level: c_uint,
+    variant: *const c_char,
+    key: *mut u8,
+    max_key_size: c_uint,
+    key_size: *mut c_uint,
+) -> c_int;
+
+const GENERATE_KEY_EX: &[u8] = b"GenerateKeyEx\0";
+
+/// Size of the key buffer given to the libraries.
+const MAX_KEY_SIZE: usize = 256;
+
+/// Shared library computing keys through a GenerateKeyEx function.
+#[derive(Debug)]
+pub struct KeyLibrary {
+    name: String,
+    library: Library,
+}
+
+impl KeyLibrary {
+    pub fn load(filename: &Path) -> Result<KeyLibrary, ScenarioError> {
+        let name = filename.display().to_string();
+        // SAFETY: the library is trusted, as it is chosen in the configuration
+        let library = unsafe { Library::new(filename) }
+            .map_err(|err| ScenarioError::SeedKey(format!("can't load {name}: {err}")))?;
+        let key_library = KeyLibrary { name, library };
+        key_library.generate_key_ex()?;
+        Ok(key_library)
+    }
+
+    fn generate_key_ex(&self) -> Result<Symbol<GenerateKeyEx>, ScenarioError> {
+        // SAFETY: the symbol is expected to have the GenerateKeyEx signature
+        unsafe { self.library.get(GENERATE_KEY_EX) }.map_err(|err| {
+            ScenarioError::SeedKey(format!("{} has no GenerateKeyEx: {err}", self.name))
+        })
+    }
+
+    /// Compute the key of a seed for a security level and a variant.
+    pub fn generate_key(
+        &self,
+        seed: &[u8],
+        level: u8,
+        variant: &str,
+    ) -> Result<Vec<u8>, ScenarioError> {
+        let generate_key_ex = self.generate_key_ex()?;
+        let variant = CString::new(variant).map_err(|err| {
+            ScenarioError::SeedKey(format!("invalid variant \"{variant}\": {err}"))
+        })?;
+        let mut key = vec![0u8; MAX_KEY_SIZE];
+        let mut key_size: c_uint = 0;
+        // SAFETY: the buffers outlive the call, and their sizes are given
+        let res = unsafe {
+            generate_key_ex(
+                seed.as_ptr(),
+                seed.len() as c_uint,
+                level as c_uint,
+                variant.as_ptr(),
+                key.as_mut_ptr(),
+                MAX_KEY_SIZE as c_uint,
+                &mut key_size,
+            )
+        };
+        if res != 0 {
+            return Err(ScenarioError::SeedKey(format!(
+                "GenerateKeyEx of {} failed with {res}",
+                self.name
+            )));
+        }
+        let key_size = key_size as usize;
+        if key_size == 0 || key_size > MAX_KEY_SIZE {
+            return Err(ScenarioError::SeedKey(format!(
+                "GenerateKeyEx of {} returned an invalid key length {key_size}",
+                self.name
+            )));
+        }
+        key.truncate(key_size);
+        Ok(key)
+    }
+}
+
+/// Compute the SecurityAccess key of a seed.
+pub async fn compute_key(
+    source: &KeySource,
+    seed: &[u8],
+    level: u8,
+    key_library: Option<&KeyLibrary>,
+) -> Result<Vec<u8>, ScenarioError> {
+    match source {
+        KeySource::Command(cmd) => command_key(cmd, seed).await,
+        KeySource::Plugin(plugin) => plugin_key(plugin, seed, level, key_library),
+    }
+}
+
+fn plugin_key(
+    plugin: &KeyPlugin,
+    seed: &[u8],
+    level: u8,
+    key_library: Option<&KeyLibrary>,
+) -> Result<Vec<u8>, ScenarioError> {
+    let key_library = key_library.ok_or_else(|| {
+        ScenarioError::SeedKey("no seed-key library configured, see --seedkey-library".to_string())
+    })?;
+    let key = key_library.generate_key(seed, level, &plugin.variant)?;
+    match plugin.key_length {
+        Some(key_length) if key.len() != key_length => Err(ScenarioError::SeedKey(format!(
+            "GenerateKeyEx of {} returned a {} bytes key instead of {key_length}",
+            key_library.name,
+            key.len()
+        ))),
+        _ => Ok(key),
+    }
+}
+
+async fn command_key(cmd: &KeyCommand, seed: &[u8]) -> Result<Vec<u8>, ScenarioError> {
+    let seed_hex: String = seed.iter().map(|b| format!("{b:02x}")).collect();
+    let mut command = Command::new(&cmd.program);
+    command
+        .args(&cmd.args)
+        .stdout(Stdio::piped())
+        .kill_on_drop(true);
+    match cmd.seed {
+        SeedInput::Stdin => command.stdin(Stdio::piped()),
+        SeedInput::Argument => command.arg(&seed_hex).stdin(Stdio::null()),
+    };
+
+    let mut child = command
+        .spawn()
+        .map_err(|err| ScenarioError::SeedKey(format!("can't run {}: {err}", cmd.program)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(format!("{seed_hex}\n").as_bytes()).await {
+            // The program doesn't read the seed, its exit status tells if it failed
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(err) => {
+                return Err(ScenarioError::SeedKey(format!(
+                    "can't give the seed to {}: {err}",
+                    cmd.program
+                )))
+            }
+            Ok(()) => {}
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|err| ScenarioError::SeedKey(format!("{} failed: {err}", cmd.program)))?;
+    if !output.status.success() {
+        return Err(ScenarioError::SeedKey(format!(
+            "{} failed: {}",
+            cmd.program, output.status
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_hex(&stdout).ok_or_else(|| {
+        ScenarioError::SeedKey(format!(
+            "{} printed an invalid key \"{}\"",
+            cmd.program,
+            stdout.trim()
+        ))
+    })
+}
+
+/// Parse hexadecimal bytes, whitespaces being ignored, such as "12 34" or
+/// "1234".
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = s.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,107 @@
+use super::common;
+use crate::scenario::error::ScenarioError;
+
+const BREAK_WHILELOOP: &str = r##"
+- !EvalExpr
+  expression: idx = 0;
+- !WhileLoop
+  condition: true
+  steps:
+  - !ReadDID
+    did: 0xf190
+  - !EvalExpr
+    expression: idx = idx + 1;
+  - !Break
+    condition: idx == 2
+- !ReadDID
+  did: 0xf012
+"##;
+const EXPECTED_BREAK_WHILELOOP: &[&str] = &["22 f1 90", "22 f1 90", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn break_whileloop() {
+    let res = common::run_test_scenario_str(BREAK_WHILELOOP).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_BREAK_WHILELOOP)));
+}
+
+const CONTINUE_FOREACH: &str = r##"
+- !ForEach
+  variable: did
+  items: !Tuple (0xf190, 0xf18c, 0xf012)
+  steps:
+  - !ReadDID
+    did: !Expr did
+  - !Continue
+    condition: reply_nth(0) == 0x7f
+  - !RawUds
+    data: !Bytes 3e 00
+"##;
+const EXPECTED_CONTINUE_FOREACH: &[&str] = &["22 f1 90", "3e 00", "22 f1 8c", "22 f0 12", "3e 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn continue_foreach() {
+    let res = common::run_test_scenario_str(CONTINUE_FOREACH).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_CONTINUE_FOREACH)));
+}
+
+const BREAK_NESTED: &str = r##"
+- !Repeat
+  count: 2
+  variable: i
+  steps:
+  - !Repeat
+    count: 3
+    variable: j
+    steps:
+    - !If
+      condition: j == 1
+      then:
+      - !Break
+    - !Try
+      steps:
+      - !Continue
+        condition: i == 1
+      finally:
+      - !ReadDID
+        did: 0xf190
+    - !ReadDID
+      did: 0xf012
+"##;
+const EXPECTED_BREAK_NESTED: &[&str] = &["22 f1 90", "22 f0 12", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn break_nested() {
+    let res = common::run_test_scenario_str(BREAK_NESTED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_BREAK_NESTED)));
+}
+
+const BREAK_OUTSIDE_LOOP: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !Break
+"##;
+
+#[test]
+fn break_outside_loop() {
+    let res = crate::scenario::parser::read_scenario_str(BREAK_OUTSIDE_LOOP);
+    assert!(matches!(res, Err(ScenarioError::InvalidStep(step, _)) if step == "Break"));
+}
+
+const CONTINUE_IN_PROCEDURE: &str = r##"
+procedures:
+  skip:
+    steps:
+    - !Continue
+steps:
+- !Repeat
+  count: 2
+  steps:
+  - !Call
+    procedure: skip
+"##;
+
+#[test]
+fn continue_in_procedure() {
+    let res = crate::scenario::parser::read_scenario_str(CONTINUE_IN_PROCEDURE);
+    assert!(matches!(res, Err(ScenarioError::InvalidStep(step, _)) if step == "Continue"));
+}
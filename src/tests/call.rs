@@ -0,0 +1,94 @@
+use super::common;
+use crate::scenario::{error::ScenarioError, parser};
+
+const CALL: &str = r##"
+procedures:
+  read_did:
+    params: [did_low]
+    steps:
+    - !EvalExpr
+      expression: request = (0x22, 0xf1, did_low);
+    - !RawUds
+      data: !EvalExprVarname request
+  read_vin:
+    steps:
+    - !ReadDID
+      did: 0xf190
+steps:
+- !EvalExpr
+  expression: request = (0x22, 0xf0, 0x12);
+- !Call
+  procedure: read_did
+  args:
+    did_low: 0x91
+  local: true
+- !Call
+  procedure: read_vin
+- !RawUds
+  data: !EvalExprVarname request
+"##;
+const EXPECTED_CALL: &[&str] = &["22 f1 91", "22 f1 90", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn call() {
+    let res = common::run_test_scenario_str(CALL).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_CALL)));
+}
+
+const CALL_MISSING_ARGUMENT: &str = r##"
+procedures:
+  read_did:
+    params: [did_low]
+    steps:
+    - !EvalExpr
+      expression: request = (0x22, 0xf1, did_low);
+    - !RawUds
+      data: !EvalExprVarname request
+steps:
+- !Call
+  procedure: read_did
+"##;
+
+#[test]
+fn call_missing_argument() {
+    let res = parser::read_scenario_str(CALL_MISSING_ARGUMENT);
+    assert!(matches!(res, Err(ScenarioError::InvalidCall(name, _)) if name == "read_did"));
+}
+
+const CALL_UNKNOWN_PROCEDURE: &str = r##"
+procedures:
+  erase:
+    steps:
+    - !RoutineControl
+      control: Start
+      routine: 0xff00
+    - !Call
+      procedure: flash
+"##;
+
+#[test]
+fn call_unknown_procedure() {
+    let res = parser::read_scenario_str(CALL_UNKNOWN_PROCEDURE);
+    assert!(matches!(res, Err(ScenarioError::InvalidCall(name, _)) if name == "flash"));
+}
+
+const CALL_RECURSION: &str = r##"
+procedures:
+  forever:
+    steps:
+    - !Call
+      procedure: forever
+steps:
+- !Call
+  procedure: forever
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn call_recursion() {
+    let (res, received) = common::run_test_scenario_str_received(CALL_RECURSION).await;
+    assert!(
+        matches!(&res, Err(ScenarioError::InvalidCall(name, _)) if name == "forever"),
+        "{res:?}"
+    );
+    assert!(received.is_empty());
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::common;
+use crate::scenario::{
+    checkpoint::{self, Checkpoint, Variable},
+    error::ScenarioError,
+    main::Settings,
+    parser,
+};
+
+fn scenario_hash(s: &str) -> u64 {
+    checkpoint::scenario_hash(&parser::read_scenario_str(s).unwrap()).unwrap()
+}
+
+fn checkpoint_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+const CHECKPOINT_WRITE: &str = r##"
+- !EvalExpr
+  expression: vin = "VF1R"; did = 0xf190;
+- !Repeat
+  count: 2
+  variable: i
+  steps:
+  - !ReadDID
+    did: !Expr did + i
+"##;
+const EXPECTED_CHECKPOINT_WRITE: &[&str] = &["22 f1 90", "22 f1 91"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn checkpoint_write() {
+    let file = checkpoint_file("diagtool_checkpoint_write.json");
+    let settings = Settings {
+        checkpoint_file: Some(file.clone()),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_with_settings(CHECKPOINT_WRITE, settings).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_CHECKPOINT_WRITE)));
+
+    let checkpoint = Checkpoint::read(&file).unwrap();
+    assert_eq!(checkpoint.scenario_hash, scenario_hash(CHECKPOINT_WRITE));
+    assert_eq!(checkpoint.step_index, 2);
+    assert_eq!(checkpoint.loop_counter, None);
+    assert_eq!(
+        checkpoint.variables,
+        BTreeMap::from([
+            ("did".to_string(), Variable::Int(0xf190)),
+            ("i".to_string(), Variable::Int(1)),
+            ("vin".to_string(), Variable::String("VF1R".to_string())),
+        ])
+    );
+}
+
+const CHECKPOINT_INTERRUPTED: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !Repeat
+  count: 3
+  variable: i
+  steps:
+  - !If
+    condition: i == 2
+    then:
+    - !RawUds
+      data: !Bytes 31 01 ff ff
+      timeout_ms: 100
+- !ReadDID
+  did: 0xf012
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn checkpoint_interrupted() {
+    let file = checkpoint_file("diagtool_checkpoint_interrupted.json");
+    let settings = Settings {
+        checkpoint_file: Some(file.clone()),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_with_settings(CHECKPOINT_INTERRUPTED, settings).await;
+    assert!(res.is_err());
+
+    let checkpoint = Checkpoint::read(&file).unwrap();
+    assert_eq!(checkpoint.step_index, 1);
+    assert_eq!(checkpoint.loop_counter, Some(2));
+}
+
+const CHECKPOINT_RESUME: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !Repeat
+  count: 4
+  variable: i
+  steps:
+  - !ReadDID
+    did: !Expr did + i
+- !ReadDID
+  did: 0xf012
+"##;
+const EXPECTED_CHECKPOINT_RESUME: &[&str] = &["22 f1 92", "22 f1 93", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn checkpoint_resume() {
+    let file = checkpoint_file("diagtool_checkpoint_resume.json");
+    let settings = Settings {
+        checkpoint_file: Some(file.clone()),
+        resume: Some(Checkpoint {
+            scenario_hash: scenario_hash(CHECKPOINT_RESUME),
+            step_index: 1,
+            loop_counter: Some(2),
+            variables: BTreeMap::from([("did".to_string(), Variable::Int(0xf190))]),
+        }),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_with_settings(CHECKPOINT_RESUME, settings).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_CHECKPOINT_RESUME)));
+    assert_eq!(Checkpoint::read(&file).unwrap().step_index, 3);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn checkpoint_resume_invalid() {
+    let settings = Settings {
+        resume: Some(Checkpoint {
+            scenario_hash: scenario_hash(CHECKPOINT_RESUME),
+            step_index: 4,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_outcome(CHECKPOINT_RESUME, settings).await;
+    assert!(matches!(res, Err(ScenarioError::InvalidCheckpoint(_))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn checkpoint_resume_other_scenario() {
+    let settings = Settings {
+        resume: Some(Checkpoint {
+            scenario_hash: scenario_hash(CHECKPOINT_WRITE),
+            step_index: 1,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_outcome(CHECKPOINT_RESUME, settings).await;
+    assert!(matches!(res, Err(ScenarioError::InvalidCheckpoint(_))));
+}
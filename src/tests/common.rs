@@ -1,20 +1,81 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
 
 use super::ecu;
-use crate::scenario::{self, parser::Steps};
+use crate::scenario::{
+    self,
+    error::ScenarioError,
+    main::{Outcome, Settings},
+    parser::{ParamValue, Scenario},
+};
 
 pub async fn run_test_scenario_str(s: &str) -> Result<Vec<Vec<u8>>, String> {
-    let steps = scenario::parser::read_scenario_str(s);
-    test_scenario(steps).await
+    run_test_scenario_str_with_parameters(s, BTreeMap::new()).await
+}
+
+pub async fn run_test_scenario_str_with_parameters(
+    s: &str,
+    parameters: BTreeMap<String, ParamValue>,
+) -> Result<Vec<Vec<u8>>, String> {
+    let scen = scenario::parser::read_scenario_str(s)
+        .map_err(|err| format!("scenario parsing failed: {err:?}"))?;
+    test_scenario(scen, parameters).await
+}
+
+pub async fn run_test_scenario_str_with_settings(
+    s: &str,
+    settings: Settings,
+) -> Result<Vec<Vec<u8>>, String> {
+    let scen = scenario::parser::read_scenario_str(s)
+        .map_err(|err| format!("scenario parsing failed: {err:?}"))?;
+    let (res, received) = run_scenario_with_ecu(scen, BTreeMap::new(), settings).await?;
+    res.map(|_| received)
+        .map_err(|err| format!("scenario finished on error: {err:?}"))
 }
 
 pub async fn run_test_scenario_file(filename: &str) -> Result<Vec<Vec<u8>>, String> {
-    let steps = scenario::parser::read_scenario(filename);
-    test_scenario(steps).await
+    let scen = scenario::parser::read_scenario(filename)
+        .map_err(|err| format!("scenario parsing failed: {err:?}"))?;
+    test_scenario(scen, BTreeMap::new()).await
+}
+
+pub async fn run_test_scenario_str_outcome(
+    s: &str,
+    settings: Settings,
+) -> Result<Outcome, ScenarioError> {
+    let scen = scenario::parser::read_scenario_str(s)?;
+    let (res, _) = run_scenario_with_ecu(scen, BTreeMap::new(), settings)
+        .await
+        .expect("ecu simulator finished on error");
+    res
+}
+
+/// Run a scenario, returning its result along with the UDS requests received
+/// by the ECU, even if the scenario failed.
+pub async fn run_test_scenario_str_received(
+    s: &str,
+) -> (Result<Outcome, ScenarioError>, Vec<Vec<u8>>) {
+    let scen = scenario::parser::read_scenario_str(s).expect("scenario parsing failed");
+    run_scenario_with_ecu(scen, BTreeMap::new(), Settings::default())
+        .await
+        .expect("ecu simulator finished on error")
+}
+
+async fn test_scenario(
+    scen: Scenario,
+    parameters: BTreeMap<String, ParamValue>,
+) -> Result<Vec<Vec<u8>>, String> {
+    let (res, received) = run_scenario_with_ecu(scen, parameters, Settings::default()).await?;
+    res.map(|_| received)
+        .map_err(|err| format!("scenario finished on error: {err:?}"))
 }
 
-async fn test_scenario(steps: Steps) -> Result<Vec<Vec<u8>>, String> {
+async fn run_scenario_with_ecu(
+    scen: Scenario,
+    parameters: BTreeMap<String, ParamValue>,
+    settings: Settings,
+) -> Result<(Result<Outcome, ScenarioError>, Vec<Vec<u8>>), String> {
     let _ = env_logger::try_init();
 
     let listener = TcpListener::bind("127.0.0.1:0")
@@ -28,18 +89,20 @@ async fn test_scenario(steps: Steps) -> Result<Vec<Vec<u8>>, String> {
 
     let received = Arc::new(Mutex::new(Vec::new()));
     tokio::select!(
-            res = async {
-        ecu::ecu(listener, received.clone())
-                    .await
-                    .map_err(|err| format!("ecu simulator finished on error: {err:?}"))
-            } => res,
-            res = async {
-        scenario::main::scenario(local_addr, remote_addr, doip_la, doip_ta, steps)
-                    .await
-                    .map_err(|err| format!("scenario finished on error: {err:?}"))
-            } => res,
+        res = ecu::ecu(listener, received.clone()) => {
+            Err(format!("ecu simulator finished: {res:?}"))
+        },
+        res = scenario::main::scenario(
+            local_addr,
+            remote_addr,
+            doip_la,
+            doip_ta,
+            scen,
+            parameters,
+            settings,
+        ) => Ok(res),
     )
-    .map(|_| received.lock().unwrap().to_owned())
+    .map(|res| (res, received.lock().unwrap().to_owned()))
 }
 
 pub fn uds_seq(strs: &[&str]) -> Vec<Vec<u8>> {
@@ -0,0 +1,129 @@
+use super::common;
+use crate::scenario::error::ScenarioError;
+
+const COMMUNICATIONCONTROL_ENABLED: &str = r"
+- !CommunicationControl
+  control: DisableRxAndTx
+- !CommunicationControl
+  control: EnableRxAndTx
+";
+const EXPECTED_COMMUNICATIONCONTROL_ENABLED: &[&str] = &["28 03 01", "28 00 01"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn communicationcontrol_enabled() {
+    let res = common::run_test_scenario_str(COMMUNICATIONCONTROL_ENABLED).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(EXPECTED_COMMUNICATIONCONTROL_ENABLED))
+    );
+}
+
+const COMMUNICATIONCONTROL_ABORTED: &str = r"
+- !CommunicationControl
+  control: DisableRxAndTx
+- !ControlDTCSetting
+  setting: Off
+  suppress_response: true
+- !RawUds
+  data: !Bytes 22 ff ff
+- !AbortIfNrc
+- !ReadDID
+  did: 0xf190
+";
+const EXPECTED_COMMUNICATIONCONTROL_ABORTED: &[&str] =
+    &["28 03 01", "85 82", "22 ff ff", "85 01", "28 00 01"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn communicationcontrol_aborted() {
+    let res = common::run_test_scenario_str(COMMUNICATIONCONTROL_ABORTED).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(EXPECTED_COMMUNICATIONCONTROL_ABORTED))
+    );
+}
+
+const COMMUNICATIONCONTROL_PARTLY_ENABLED: &str = r"
+- !CommunicationControl
+  control: DisableRxAndTx
+  communication: NormalAndNetworkManagement
+- !CommunicationControl
+  control: DisableRxAndTx
+  communication: Normal
+  subnet: 0x2
+- !CommunicationControl
+  control: EnableRxAndTx
+  communication: Normal
+";
+const EXPECTED_COMMUNICATIONCONTROL_PARTLY_ENABLED: &[&str] =
+    &["28 03 03", "28 03 21", "28 00 01", "28 00 02", "28 00 21"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn communicationcontrol_partly_enabled() {
+    let res = common::run_test_scenario_str(COMMUNICATIONCONTROL_PARTLY_ENABLED).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(
+            EXPECTED_COMMUNICATIONCONTROL_PARTLY_ENABLED
+        ))
+    );
+}
+
+const COMMUNICATIONCONTROL_ENABLED_TOGETHER: &str = r"
+- !CommunicationControl
+  control: DisableRxAndTx
+  communication: Normal
+- !CommunicationControl
+  control: DisableRxAndTx
+  communication: NetworkManagement
+- !CommunicationControl
+  control: EnableRxAndTx
+  communication: NormalAndNetworkManagement
+";
+const EXPECTED_COMMUNICATIONCONTROL_ENABLED_TOGETHER: &[&str] =
+    &["28 03 01", "28 03 02", "28 00 03"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn communicationcontrol_enabled_together() {
+    let res = common::run_test_scenario_str(COMMUNICATIONCONTROL_ENABLED_TOGETHER).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(
+            EXPECTED_COMMUNICATIONCONTROL_ENABLED_TOGETHER
+        ))
+    );
+}
+
+// The ECU may have disabled the communication even if its reply is missing,
+// the communication being enabled anyway.
+const COMMUNICATIONCONTROL_REFUSED: &str = r"
+- !CommunicationControl
+  control: EnableRxAndDisableTx
+";
+const EXPECTED_COMMUNICATIONCONTROL_REFUSED: &[&str] = &["28 01 01", "28 00 01"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn communicationcontrol_refused() {
+    let (res, received) =
+        common::run_test_scenario_str_received(COMMUNICATIONCONTROL_REFUSED).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x11))), "{res:?}");
+    assert_eq!(
+        received,
+        common::uds_seq(EXPECTED_COMMUNICATIONCONTROL_REFUSED)
+    );
+}
+
+const CONTROLDTCSETTING_REFUSED: &str = r"
+- !ControlDTCSetting
+  setting: Off
+";
+const EXPECTED_CONTROLDTCSETTING_REFUSED: &[&str] = &["85 02", "85 01"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn controldtcsetting_refused() {
+    let (res, received) = common::run_test_scenario_str_received(CONTROLDTCSETTING_REFUSED).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x11))), "{res:?}");
+    assert_eq!(
+        received,
+        common::uds_seq(EXPECTED_CONTROLDTCSETTING_REFUSED)
+    );
+}
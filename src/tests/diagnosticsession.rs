@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use super::common;
+use crate::scenario::{error::ScenarioError, main::Settings};
+
+const DIAGNOSTICSESSION: &str = r##"
+- !If
+  condition: session == 1
+  then:
+  - !DiagnosticSession
+    session: Extended
+- !If
+  condition: session == 3
+  then:
+  - !ReadDID
+    did: 0xf190
+- !DiagnosticSession
+  session: Default
+"##;
+const EXPECTED_DIAGNOSTICSESSION: &[&str] = &["10 03", "22 f1 90", "10 01"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn diagnosticsession() {
+    let res = common::run_test_scenario_str(DIAGNOSTICSESSION).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_DIAGNOSTICSESSION)));
+}
+
+const DIAGNOSTICSESSION_REFUSED: &str = r##"
+- !DiagnosticSession
+  session: !Oem 0x40
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn diagnosticsession_refused() {
+    let res =
+        common::run_test_scenario_str_outcome(DIAGNOSTICSESSION_REFUSED, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x11))));
+}
+
+// The ECU answers responsePending, and nothing more, which is detected after
+// the P2* of the extended session rather than after the default 5s.
+const DIAGNOSTICSESSION_TIMINGS: &str = r##"
+- !DiagnosticSession
+  session: Extended
+- !RawUds
+  data: !Bytes 31 01 ff ff
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn diagnosticsession_timings() {
+    let settings = Settings {
+        timeout: Some(Duration::from_millis(2000)),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_outcome(DIAGNOSTICSESSION_TIMINGS, settings).await;
+    assert!(matches!(res, Err(ScenarioError::NoReply(0x31))));
+}
+
+// A reply to another service is not taken for the reply of the request.
+const DIAGNOSTICSESSION_OTHER_REPLY: &str = r##"
+- !DiagnosticSession
+  session: Extended
+- !RawUds
+  data: !Bytes 31 01 ff 03
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn diagnosticsession_other_reply() {
+    let res =
+        common::run_test_scenario_str_outcome(DIAGNOSTICSESSION_OTHER_REPLY, Settings::default())
+            .await;
+    assert!(matches!(res, Err(ScenarioError::NoReply(0x31))));
+}
+
+const DIAGNOSTICSESSION_SUPPRESSED: &str = r##"
+- !DiagnosticSession
+  session: Extended
+  suppress_response: true
+- !If
+  condition: session == 3
+  then:
+  - !ReadDID
+    did: 0xf190
+"##;
+const EXPECTED_DIAGNOSTICSESSION_SUPPRESSED: &[&str] = &["10 83", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn diagnosticsession_suppressed() {
+    let res = common::run_test_scenario_str(DIAGNOSTICSESSION_SUPPRESSED).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(EXPECTED_DIAGNOSTICSESSION_SUPPRESSED))
+    );
+}
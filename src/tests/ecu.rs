@@ -12,7 +12,7 @@ use tokio::{net::TcpListener, task};
 
 use doip_rw_tokio::{DoIpTcpConnection, Timings};
 
-const UDS_ANSWERS: [(&str, &str); 7] = [
+const UDS_ANSWERS: [(&str, &str); 47] = [
     (
         r"22f012",
         "62 f0 12 32 36 34 31 33 30 30 35 30 30 52 31", //"62140350001R"
@@ -22,11 +22,64 @@ const UDS_ANSWERS: [(&str, &str); 7] = [
         "62 f1 90 56 46 31 58 52 32 31 30 46 53 54 47 42 45 4e 30 34", // VF1XR210FSTGBEN04
     ),
     (r"22.*", "7f2210"),
+    // Sessions, with P2 = 50ms and P2* = 5s, or P2* = 100ms in extended session
+    (r"^10 01$", "50 01 00 32 01 f4"),
+    (r"^10 02$", "50 02 00 32 01 f4"),
+    (r"^10 03$", "50 03 00 32 00 0a"),
+    // Security access, the key being the seed xor ff ff ff ff
+    (r"^27 01$", "67 01 12 34 56 78"),
+    (r"^27 02 ed cb a9 87$", "67 02"),
+    (r"^27 02", "7f 27 35"),
+    (r"^27 03$", "67 03 00 00 00 00"),
+    (r"^27 05$", "7f 27 37"),
+    (r"^11 01$", "51 01"),
+    // Suppressed positive responses, an empty answer not being sent
+    (r"^11 81$", ""),
+    (r"^3e 80$", ""),
+    (r"^85 82$", ""),
+    (r"^10 83$", ""),
+    (r"^31 81 ff 00", ""),
+    // DTCs, P0123-45 confirmed and U0100-16 failed
+    (r"^19 01 08$", "59 01 ff 01 00 02"),
+    (r"^19 02 08$", "59 02 ff 01 23 45 08 c1 00 16 2f"),
+    (
+        r"^19 04 01 23 45 ff$",
+        "59 04 01 23 45 08 01 01 f1 90 56 46",
+    ),
+    (r"^19 06 01 23 45 01$", "59 06 01 23 45 08 01 05"),
+    (r"^14 ff ff ff$", "54"),
+    (r"^14", "7f 14 31"),
+    // Communication and DTC setting, for the flash preconditions
+    (r"^28 00", "68 00"),
+    (r"^28 03", "68 03"),
+    (r"^85 01$", "c5 01"),
+    // Fan output
+    (r"^2f f0 10 00", "6f f0 10 00 00"),
+    (r"^2f f0 10 02$", "6f f0 10 02 01"),
+    (r"^2f f0 10 03", "6f f0 10 03 01"),
     (r"19 0a", "59 0a ff ea 19 88 00 fd 01 50"),
     // Transfer file:
     (r"34.*", "74 20 0f fa"),
+    (r"^35", "75 20 00 06"),
+    (r"^36 01$", "76 01 de ad ba be"),
+    (r"^36 02$", "76 02 ca fe"),
+    (r"^36 03$", "76 03"),
     (r"36.*", "76 01"),
     (r"37.*", "77"),
+    // Calibration memory, read by chunks of 4 bytes
+    (r"^23 24 00 00 40 00 00 04$", "63 de ad ba be"),
+    (r"^23 24 00 00 40 04 00 02$", "63 ca fe"),
+    (r"^3d 12 40 00 02 01 02$", "7d 12 40 00 02"),
+    (r"^3d 12 40 02 01 03$", "7d 12 40 02 01"),
+    // Routine never finishing, for timeouts:
+    (r"^31 01 ff ff$", "7f 31 78"),
+    // Erase memory routine, in progress when polled
+    (r"^31 01 ff 00", "71 01 ff 00 01"),
+    (r"^31 03 ff 00$", "71 03 ff 00 01"),
+    (r"^31 02 ff 00$", "71 02 ff 00"),
+    (r"^31 03 ff 01$", "7f 31 21"),
+    // Reply to another service, not answering the routine request
+    (r"^31 01 ff 03$", "62 f1 90"),
 ];
 
 fn print_uds_request(prefix: &str, req: &[u8]) {
@@ -73,6 +126,7 @@ async fn handle_diag_request(
     print_uds_request("UDS  input: ", uds);
     let answer = match find_uds_answer(uds) {
         None => vec![0x7f, uds[0], 0x11],
+        Some(answer) if answer.is_empty() => return false,
         Some(answer) => answer,
     };
     print_uds_request("UDS output: ", &answer);
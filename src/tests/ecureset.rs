@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+use super::common;
+use crate::scenario::{
+    error::ScenarioError,
+    main::{Outcome, Settings},
+};
+
+const ECURESET: &str = r"
+- !DiagnosticSession
+  session: Extended
+- !EcuReset
+  reset_type: Hard
+  max_wait_ms: 5000
+- !If
+  condition: session == 1
+  then:
+  - !ReadDID
+    did: 0xf190
+";
+const EXPECTED_ECURESET: &[&str] = &["10 03", "11 01", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn ecureset() {
+    let res = common::run_test_scenario_str(ECURESET).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_ECURESET)));
+}
+
+const ECURESET_SUPPRESSED: &str = r"
+- !EcuReset
+  reset_type: Hard
+  suppress_response: true
+  max_wait_ms: 5000
+- !ReadDID
+  did: 0xf190
+";
+const EXPECTED_ECURESET_SUPPRESSED: &[&str] = &["11 81", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn ecureset_suppressed() {
+    let res = common::run_test_scenario_str(ECURESET_SUPPRESSED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_ECURESET_SUPPRESSED)));
+}
+
+const ECURESET_NO_ANNOUNCEMENT: &str = r"
+- !EcuReset
+  reset_type: Hard
+  max_wait_ms: 200
+  wait_announcement: true
+- !ReadDID
+  did: 0xf190
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn ecureset_no_announcement() {
+    let res =
+        common::run_test_scenario_str_outcome(ECURESET_NO_ANNOUNCEMENT, Settings::default()).await;
+    assert!(
+        matches!(&res, Err(ScenarioError::Timeout(step)) if step == "EcuReset at step 1"),
+        "{res:?}"
+    );
+}
+
+const ECURESET_RESTORE: &str = r"
+- !CommunicationControl
+  control: DisableRxAndTx
+- !EcuReset
+  reset_type: Hard
+  max_wait_ms: 5000
+- !RawUds
+  data: !Bytes 22 ff ff
+- !AbortIfNrc
+";
+const EXPECTED_ECURESET_RESTORE: &[&str] = &["28 03 01", "11 01", "22 ff ff"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn ecureset_restore() {
+    let (res, received) = common::run_test_scenario_str_received(ECURESET_RESTORE).await;
+    assert!(matches!(res, Ok(Outcome::Aborted)), "{res:?}");
+    assert_eq!(received, common::uds_seq(EXPECTED_ECURESET_RESTORE));
+}
+
+const ECURESET_DELAY: &str = r"
+- !EcuReset
+  reset_type: Hard
+  max_wait_ms: 5000
+  reset_delay_ms: 300
+- !ReadDID
+  did: 0xf190
+";
+const EXPECTED_ECURESET_DELAY: &[&str] = &["11 01", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn ecureset_delay() {
+    let start = Instant::now();
+    let res = common::run_test_scenario_str(ECURESET_DELAY).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_ECURESET_DELAY)));
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
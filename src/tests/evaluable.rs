@@ -0,0 +1,95 @@
+use std::io::Write;
+
+use super::common;
+
+const EVALUABLE_DID: &str = r##"
+- !EvalExpr
+  expression: base_did = 0xf190; vin = "VF1R";
+- !ForEach
+  variable: i
+  items: !Range
+    start: 0
+    end: 3
+  steps:
+  - !ReadDID
+    did: !Expr base_did + i
+- !WriteDID
+  did: !Expr base_did
+  data: !EvalExprVarname vin
+"##;
+const EXPECTED_EVALUABLE_DID: &[&str] =
+    &["22 f1 90", "22 f1 91", "22 f1 92", "2e f1 90 56 46 31 52"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn evaluable_did() {
+    let res = common::run_test_scenario_str(EVALUABLE_DID).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_EVALUABLE_DID)));
+}
+
+const EVALUABLE_SLEEPMS: &str = r##"
+- !EvalExpr
+  expression: delay = 5;
+- !SleepMs delay * 2
+- !ReadDID
+  did: 0xf190
+"##;
+const EXPECTED_EVALUABLE_SLEEPMS: &[&str] = &["22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn evaluable_sleepms() {
+    let res = common::run_test_scenario_str(EVALUABLE_SLEEPMS).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_EVALUABLE_SLEEPMS)));
+}
+
+const EVALUABLE_TRANSFERDOWNLOAD: &str = r##"
+- !EvalExpr
+  expression: block = "FD02"; base_addr = 0x10;
+- !TransferDownload
+  compression_method: 1
+  encrypt_method: 2
+  addr: !Expr base_addr + 3
+  filename: !Expr '"/tmp/" + block + ".bin"'
+  memorysize: !Expr 2 * 2
+"##;
+const EXPECTED_EVALUABLE_TRANSFERDOWNLOAD: &[&str] = &[
+    "34 12 44 00 00 00 13 00 00 00 04", // TransferStart
+    "36 01 ca fe de ca",                // TransferData of 0xca 0xfe 0xde 0xca
+    "37",                               // TransferExit
+];
+const EVALUABLE_TRANSFERDOWNLOAD_BIN: &[&str] = &["ca fe de ca"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn evaluable_transferdownload() {
+    {
+        let mut file = std::fs::File::create("/tmp/FD02.bin").unwrap();
+        file.write_all(&common::uds_seq(EVALUABLE_TRANSFERDOWNLOAD_BIN)[0])
+            .unwrap();
+    }
+    let res = common::run_test_scenario_str(EVALUABLE_TRANSFERDOWNLOAD).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(EXPECTED_EVALUABLE_TRANSFERDOWNLOAD))
+    );
+}
+
+const EVALUABLE_INVALID_VALUE: &str = r##"
+- !ReadDID
+  did: !Expr 0x10000
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn evaluable_invalid_value() {
+    let res = common::run_test_scenario_str(EVALUABLE_INVALID_VALUE).await;
+    assert!(res.is_err());
+}
+
+const EVALUABLE_UNTAGGED_EXPR: &str = r##"
+- !ReadDID
+  did: f190
+"##;
+
+#[test]
+fn evaluable_untagged_expr() {
+    let res = crate::scenario::parser::read_scenario_str(EVALUABLE_UNTAGGED_EXPR);
+    assert!(res.is_err());
+}
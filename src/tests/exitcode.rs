@@ -0,0 +1,62 @@
+use super::common;
+use crate::scenario::{error::ScenarioError, main::Settings};
+use crate::{exit_code, scenario_exit_code};
+
+const SUCCESS: &str = r"
+- !ReadDID
+  did: 0xf190
+";
+
+const ASSERTIONS_FAILED: &str = r"
+- !ReadDID
+  did: 0xf190
+- !ExpectReply
+  starts_with: !Bytes 7f
+";
+
+const ABORTED: &str = r"
+- !RawUds
+  data: !Bytes 22 ff ff
+- !AbortIfNrc
+";
+
+const MISSING_PARAMETER: &str = r"
+parameters:
+  did:
+steps:
+- !SleepMs 1
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn exitcode_outcomes() {
+    let res = common::run_test_scenario_str_outcome(SUCCESS, Settings::default()).await;
+    assert_eq!(scenario_exit_code(&res), exit_code::SUCCESS);
+    let res = common::run_test_scenario_str_outcome(ASSERTIONS_FAILED, Settings::default()).await;
+    assert_eq!(scenario_exit_code(&res), exit_code::ASSERTIONS_FAILED);
+    let res = common::run_test_scenario_str_outcome(ABORTED, Settings::default()).await;
+    assert_eq!(scenario_exit_code(&res), exit_code::ABORTED);
+    let res = common::run_test_scenario_str_outcome(MISSING_PARAMETER, Settings::default()).await;
+    assert_eq!(scenario_exit_code(&res), exit_code::CONFIG);
+}
+
+#[test]
+fn exitcode_errors() {
+    let code = |err| scenario_exit_code(&Err(err));
+    assert_eq!(code(ScenarioError::Nrc(0x31)), exit_code::ECU_REPLY);
+    assert_eq!(
+        code(ScenarioError::NetworkConnectorDead),
+        exit_code::NETWORK
+    );
+    assert_eq!(
+        code(ScenarioError::RoutingActivationFailed),
+        exit_code::NETWORK
+    );
+    assert_eq!(
+        code(ScenarioError::IncludeCycle("a -> a".to_string())),
+        exit_code::CONFIG
+    );
+    assert_eq!(
+        code(ScenarioError::Io(std::io::ErrorKind::NotFound.into())),
+        exit_code::IO
+    );
+}
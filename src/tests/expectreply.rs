@@ -0,0 +1,44 @@
+use super::common;
+
+const EXPECTREPLY: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !ExpectReply
+  starts_with: !Bytes 62 f1 90
+  bytes:
+  - index: 3
+    value: 0x50
+    mask: 0xf0
+  - index: 4
+    value: 0x46
+  regex: 62 f1 90 56 46 31 .*
+- !ExpectReply
+  starts_with: !Bytes 62 f1 91
+  on_failure: Continue
+- !ReadDID
+  did: 0xf012
+- !ExpectReply
+  regex: ^7f
+- !ReadDID
+  did: 0xf190
+"##;
+const EXPECTED_EXPECTREPLY: &[&str] = &["22 f1 90", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn expectreply() {
+    let res = common::run_test_scenario_str(EXPECTREPLY).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_EXPECTREPLY)));
+}
+
+const EXPECTREPLY_INVALID_REGEX: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !ExpectReply
+  regex: 62 f1 (90
+"##;
+
+#[test]
+fn expectreply_invalid_regex() {
+    let res = crate::scenario::parser::read_scenario_str(EXPECTREPLY_INVALID_REGEX);
+    assert!(res.is_err());
+}
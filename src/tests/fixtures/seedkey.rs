@@ -0,0 +1,36 @@
+//! Seed-key library of the tests, built by the SecurityAccess tests.
+//!
+//! The key is the seed xor 0xff, truncated to 2 bytes for the "SHORT" variant.
+#![allow(non_snake_case)]
+
+use std::ffi::{c_char, c_int, c_uint, CStr};
+
+/// # Safety
+///
+/// The pointers must be valid for the given sizes, and variant must be a nul
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn GenerateKeyEx(
+    seed: *const u8,
+    seed_size: c_uint,
+    _level: c_uint,
+    variant: *const c_char,
+    key: *mut u8,
+    max_key_size: c_uint,
+    key_size: *mut c_uint,
+) -> c_int {
+    let seed = std::slice::from_raw_parts(seed, seed_size as usize);
+    let size = match CStr::from_ptr(variant).to_bytes() {
+        b"SHORT" => seed.len().min(2),
+        _ => seed.len(),
+    };
+    if size > max_key_size as usize {
+        return 1;
+    }
+    let key = std::slice::from_raw_parts_mut(key, size);
+    for (k, s) in key.iter_mut().zip(seed) {
+        *k = s ^ 0xff;
+    }
+    *key_size = size as c_uint;
+    0
+}
@@ -0,0 +1,64 @@
+use super::common;
+
+const FOREACH_TUPLE: &str = r##"
+- !EvalExpr
+  expression: request = (0x22, 0xf1, 0x90);
+- !ForEach
+  variable: did
+  items: !Tuple (0xf190, 0xf012)
+  steps:
+  - !EvalExpr
+    expression: request = (0x22, did / 256, did % 256);
+  - !RawUds
+    data: !EvalExprVarname request
+"##;
+const EXPECTED_FOREACH_TUPLE: &[&str] = &["22 f1 90", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn foreach_tuple() {
+    let res = common::run_test_scenario_str(FOREACH_TUPLE).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_FOREACH_TUPLE)));
+}
+
+const FOREACH_RANGE: &str = r##"
+- !EvalExpr
+  expression: request = (0x22, 0xf1, 0x90);
+- !ForEach
+  variable: i
+  items: !Range
+    start: 0x92
+    end: 0x8c
+    step: -3
+  steps:
+  - !EvalExpr
+    expression: request = (0x22, 0xf1, i);
+  - !RawUds
+    data: !EvalExprVarname request
+"##;
+const EXPECTED_FOREACH_RANGE: &[&str] = &["22 f1 92", "22 f1 8f"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn foreach_range() {
+    let res = common::run_test_scenario_str(FOREACH_RANGE).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_FOREACH_RANGE)));
+}
+
+const FOREACH_RANGE_LARGE: &str = r##"
+- !ForEach
+  variable: i
+  items: !Range
+    start: 0
+    end: 0x7fffffffffffffff
+  steps:
+  - !ReadDID
+    did: 0xf190
+  - !Break
+    condition: i == 1
+"##;
+const EXPECTED_FOREACH_RANGE_LARGE: &[&str] = &["22 f1 90", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn foreach_range_large() {
+    let res = common::run_test_scenario_str(FOREACH_RANGE_LARGE).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_FOREACH_RANGE_LARGE)));
+}
@@ -0,0 +1,54 @@
+use super::common;
+
+const IFELSE: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !If
+  condition: reply_nth(0) == 0x62
+  then:
+  - !ReadDID
+    did: 0xf012
+  else:
+  - !ReadDID
+    did: 0xf191
+- !If
+  condition: reply_nth(0) == 0x7f
+  then:
+  - !ReadDID
+    did: 0xf012
+  else:
+  - !ReadDID
+    did: 0xf191
+- !If
+  condition: reply_nth(0) == 0x7f
+  then:
+  - !ReadDID
+    did: 0xf190
+"##;
+const EXPECTED_IFELSE: &[&str] = &["22 f1 90", "22 f0 12", "22 f1 91", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn ifelse() {
+    let res = common::run_test_scenario_str(IFELSE).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_IFELSE)));
+}
+
+const IF_ABORT: &str = r##"
+- !RawUds
+  data: !Bytes 22 ff ff
+- !If
+  condition: reply_nth(0) == 0x7f
+  then:
+  - !AbortIfNrc
+  - !ReadDID
+    did: 0xf190
+- !ReadDID
+  did: 0xf190
+"##;
+const EXPECTED_IF_ABORT: &[&str] = &["22 ff ff"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn if_abort() {
+    let res = common::run_test_scenario_str(IF_ABORT).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_IF_ABORT)));
+}
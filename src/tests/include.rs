@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use super::common;
+use crate::scenario::{error::ScenarioError, parser};
+
+fn write_scenarios(dirname: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(dirname);
+    for (filename, content) in files {
+        let path = dir.join(filename);
+        std::fs::create_dir_all(path.parent().unwrap_or(Path::new("."))).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+const INCLUDE_MAIN: &str = r##"
+- !EvalExpr
+  expression: request = (0x22, 0xf1, 0x90);
+- !Include
+  filename: sub/read_did.yaml
+  args:
+    did_low: 0x91
+  local: true
+- !RawUds
+  data: !EvalExprVarname request
+- !Include
+  filename: sub/read_did.yaml
+  args:
+    did_low: 0x12
+- !RawUds
+  data: !EvalExprVarname request
+"##;
+const INCLUDE_READ_DID: &str = r##"
+- !EvalExpr
+  expression: request = (0x22, 0xf1, did_low);
+- !RawUds
+  data: !EvalExprVarname request
+- !Include
+  filename: read_vin.yaml
+"##;
+const INCLUDE_READ_VIN: &str = r##"
+- !ReadDID
+  did: 0xf190
+"##;
+const EXPECTED_INCLUDE: &[&str] = &[
+    "22 f1 91", "22 f1 90", "22 f1 90", "22 f1 12", "22 f1 90", "22 f1 12",
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn include() {
+    let dir = write_scenarios(
+        "diagtool_include",
+        &[
+            ("main.yaml", INCLUDE_MAIN),
+            ("sub/read_did.yaml", INCLUDE_READ_DID),
+            ("sub/read_vin.yaml", INCLUDE_READ_VIN),
+        ],
+    );
+    let file = dir.join("main.yaml");
+    let res = common::run_test_scenario_file(file.to_str().unwrap()).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_INCLUDE)));
+}
+
+const INCLUDE_CYCLE_A: &str = r##"
+- !Include
+  filename: b.yaml
+"##;
+const INCLUDE_CYCLE_B: &str = r##"
+- !ReadDID
+  did: 0xf190
+- !Repeat
+  count: 2
+  steps:
+  - !Include
+    filename: a.yaml
+"##;
+
+#[test]
+fn include_cycle() {
+    let dir = write_scenarios(
+        "diagtool_include_cycle",
+        &[("a.yaml", INCLUDE_CYCLE_A), ("b.yaml", INCLUDE_CYCLE_B)],
+    );
+    let file = dir.join("a.yaml");
+    let res = parser::read_scenario(file.to_str().unwrap());
+    assert!(matches!(res, Err(ScenarioError::IncludeCycle(_))));
+}
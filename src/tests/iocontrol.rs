@@ -0,0 +1,102 @@
+use super::common;
+use crate::scenario::error::ScenarioError;
+
+const IOCONTROL_RETURNED: &str = r"
+- !IoControl
+  did: 0xf010
+  control: ShortTermAdjustment
+  state: !Bytes 01
+  mask: !Bytes ff
+- !IoControl
+  did: 0xf010
+  control: ReturnControlToEcu
+";
+const EXPECTED_IOCONTROL_RETURNED: &[&str] = &["2f f0 10 03 01 ff", "2f f0 10 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn iocontrol_returned() {
+    let res = common::run_test_scenario_str(IOCONTROL_RETURNED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_IOCONTROL_RETURNED)));
+}
+
+const IOCONTROL_AT_END: &str = r"
+- !IoControl
+  did: 0xf010
+  control: ShortTermAdjustment
+  state: !Bytes 01
+- !IoControl
+  did: 0xf010
+  control: FreezeCurrentState
+";
+const EXPECTED_IOCONTROL_AT_END: &[&str] = &["2f f0 10 03 01", "2f f0 10 02", "2f f0 10 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn iocontrol_at_end() {
+    let res = common::run_test_scenario_str(IOCONTROL_AT_END).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_IOCONTROL_AT_END)));
+}
+
+const IOCONTROL_ABORTED: &str = r"
+- !IoControl
+  did: 0xf010
+  control: ShortTermAdjustment
+  state: !Bytes 01
+- !RawUds
+  data: !Bytes 22 ff ff
+- !AbortIfNrc
+- !ReadDID
+  did: 0xf190
+";
+const EXPECTED_IOCONTROL_ABORTED: &[&str] = &["2f f0 10 03 01", "22 ff ff", "2f f0 10 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn iocontrol_aborted() {
+    let res = common::run_test_scenario_str(IOCONTROL_ABORTED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_IOCONTROL_ABORTED)));
+}
+
+const IOCONTROL_MASK_AT_END: &str = r"
+- !IoControl
+  did: 0xf010
+  control: ShortTermAdjustment
+  state: !Bytes 01 02
+  mask: !Bytes 80 00
+- !IoControl
+  did: 0xf010
+  control: ShortTermAdjustment
+  state: !Bytes 01 02
+  mask: !Bytes 00 01
+- !IoControl
+  did: 0xf010
+  control: ReturnControlToEcu
+  mask: !Bytes 80 00
+";
+const EXPECTED_IOCONTROL_MASK_AT_END: &[&str] = &[
+    "2f f0 10 03 01 02 80 00",
+    "2f f0 10 03 01 02 00 01",
+    "2f f0 10 00 80 00",
+    "2f f0 10 00 00 01",
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn iocontrol_mask_at_end() {
+    let res = common::run_test_scenario_str(IOCONTROL_MASK_AT_END).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_IOCONTROL_MASK_AT_END)));
+}
+
+// The ECU may have taken the control even if its reply is missing, the control
+// being returned to the ECU anyway.
+const IOCONTROL_REFUSED: &str = r"
+- !IoControl
+  did: 0xf011
+  control: ShortTermAdjustment
+  state: !Bytes 01
+";
+const EXPECTED_IOCONTROL_REFUSED: &[&str] = &["2f f0 11 03 01", "2f f0 11 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn iocontrol_refused() {
+    let (res, received) = common::run_test_scenario_str_received(IOCONTROL_REFUSED).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x11))), "{res:?}");
+    assert_eq!(received, common::uds_seq(EXPECTED_IOCONTROL_REFUSED));
+}
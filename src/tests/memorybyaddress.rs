@@ -0,0 +1,116 @@
+use super::common;
+use crate::scenario::{error::ScenarioError, main::Settings};
+
+const READMEMORYBYADDRESS: &str = r##"
+- !ReadMemoryByAddress
+  addr: 0x4000
+  size: 6
+  chunk_size: 4
+  filename: /tmp/readmemory.bin
+  variable: memory
+- !RawUds
+  data: !EvalExprVarname memory
+"##;
+const EXPECTED_READMEMORYBYADDRESS: &[&str] = &[
+    "23 24 00 00 40 00 00 04", // ReadMemoryByAddress
+    "23 24 00 00 40 04 00 02", // ReadMemoryByAddress
+    "de ad ba be ca fe",       // Read data
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn readmemorybyaddress() {
+    let res = common::run_test_scenario_str(READMEMORYBYADDRESS).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_READMEMORYBYADDRESS)));
+    let read = std::fs::read("/tmp/readmemory.bin").unwrap();
+    assert_eq!(read, vec![0xde, 0xad, 0xba, 0xbe, 0xca, 0xfe]);
+}
+
+const WRITEMEMORYBYADDRESS: &str = r##"
+- !WriteMemoryByAddress
+  addr: 0x4000
+  data: !Bytes 01 02 03
+  address_bytes: 2
+  size_bytes: 1
+  chunk_size: 2
+"##;
+const EXPECTED_WRITEMEMORYBYADDRESS: &[&str] = &["3d 12 40 00 02 01 02", "3d 12 40 02 01 03"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn writememorybyaddress() {
+    let res = common::run_test_scenario_str(WRITEMEMORYBYADDRESS).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_WRITEMEMORYBYADDRESS)));
+}
+
+const MEMORYBYADDRESS_ADDRESS_TOO_LARGE: &str = r##"
+- !ReadMemoryByAddress
+  addr: 0x10000
+  size: 4
+  address_bytes: 2
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn memorybyaddress_address_too_large() {
+    let res = common::run_test_scenario_str_outcome(
+        MEMORYBYADDRESS_ADDRESS_TOO_LARGE,
+        Settings::default(),
+    )
+    .await;
+    assert!(
+        matches!(res, Err(ScenarioError::InvalidStep(step, _)) if step == "ReadMemoryByAddress")
+    );
+}
+
+const MEMORYBYADDRESS_INVALID_FORMAT: &str = r##"
+- !WriteMemoryByAddress
+  addr: 0x4000
+  data: !Bytes 01 02 03
+  size_bytes: 9
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn memorybyaddress_invalid_format() {
+    let res =
+        common::run_test_scenario_str_outcome(MEMORYBYADDRESS_INVALID_FORMAT, Settings::default())
+            .await;
+    assert!(
+        matches!(res, Err(ScenarioError::InvalidStep(step, _)) if step == "WriteMemoryByAddress")
+    );
+}
+
+const MEMORYBYADDRESS_ADDRESS_OVERFLOW: &str = r##"
+- !WriteMemoryByAddress
+  addr: 0xfffffffffffffffe
+  data: !Bytes 01 02 03
+  address_bytes: 8
+  chunk_size: 2
+"##;
+#[tokio::test(flavor = "current_thread")]
+async fn memorybyaddress_address_overflow() {
+    let (res, received) =
+        common::run_test_scenario_str_received(MEMORYBYADDRESS_ADDRESS_OVERFLOW).await;
+    assert!(
+        matches!(&res, Err(ScenarioError::InvalidStep(step, _)) if step == "WriteMemoryByAddress"),
+        "{res:?}"
+    );
+    assert!(received.is_empty());
+}
+
+// The area starts within the 2 bytes addresses, but ends beyond.
+const MEMORYBYADDRESS_ADDRESS_TOO_LONG: &str = r##"
+- !ReadMemoryByAddress
+  addr: 0xfffe
+  size: 4
+  address_bytes: 2
+  chunk_size: 2
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn memorybyaddress_address_too_long() {
+    let (res, received) =
+        common::run_test_scenario_str_received(MEMORYBYADDRESS_ADDRESS_TOO_LONG).await;
+    assert!(
+        matches!(&res, Err(ScenarioError::InvalidStep(step, _)) if step == "ReadMemoryByAddress"),
+        "{res:?}"
+    );
+    assert!(received.is_empty());
+}
@@ -1,13 +1,37 @@
 mod abortifnrc;
 mod all_references;
+mod breakcontinue;
+mod call;
+mod checkpoint;
 mod common;
+mod communicationcontrol;
+mod diagnosticsession;
 mod disconnectdoip;
 mod ecu;
+mod ecureset;
 mod evalexpr;
+mod evaluable;
+mod exitcode;
+mod expectreply;
+mod foreach;
+mod ifelse;
+mod include;
+mod iocontrol;
+mod memorybyaddress;
+mod parameters;
 mod printlastreply;
 mod rawuds;
 mod readdid;
+mod readdtc;
+mod repeat;
+mod retry;
+mod routinecontrol;
+mod securityaccess;
 mod sleepms;
+mod testerpresent;
+mod timeout;
 mod transferdownload;
+mod transferupload;
+mod tryonerror;
 mod whileloop;
 mod writedid;
@@ -0,0 +1,36 @@
+use std::collections::BTreeMap;
+
+use super::common;
+use crate::scenario::parser::ParamValue;
+
+const PARAMETERS: &str = r##"
+parameters:
+  did_low:
+  prefix: [0x22, 0xf1]
+steps:
+- !EvalExpr
+  expression: request = (prefix, did_low);
+- !RawUds
+  data: !EvalExprVarname request
+- !WriteDID
+  did: 0xf190
+  data: !EvalExprVarname vin
+"##;
+const EXPECTED_PARAMETERS: &[&str] = &["22 f1 91", "2e f1 90 56 46 31 52"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn parameters() {
+    let parameters = BTreeMap::from([
+        ("did_low".to_string(), ParamValue::parse("0x91").unwrap()),
+        ("vin".to_string(), ParamValue::parse("VF1R").unwrap()),
+    ]);
+    let res = common::run_test_scenario_str_with_parameters(PARAMETERS, parameters).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_PARAMETERS)));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn parameters_missing() {
+    let parameters = BTreeMap::from([("vin".to_string(), ParamValue::parse("VF1R").unwrap())]);
+    let res = common::run_test_scenario_str_with_parameters(PARAMETERS, parameters).await;
+    assert!(res.is_err_and(|err| err.contains("MissingParameter")));
+}
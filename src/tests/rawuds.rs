@@ -1,6 +1,7 @@
 use std::io::Write;
 
 use super::common;
+use crate::scenario::main::{Outcome, Settings};
 
 const RAWUDS_BYTES: &str = r##"
 - !RawUds
@@ -44,3 +45,36 @@ async fn evalexpr() {
     let res = common::run_test_scenario_str(RAWUDS_EVALEXPR).await;
     assert_eq!(res, Ok(common::uds_seq(EXPECTED_RAWUDS_EVALEXPR)));
 }
+
+const RAWUDS_SUPPRESSED: &str = r##"
+- !RawUds
+  data: !Bytes 3e 80
+- !RawUds
+  data: !Bytes 85 82
+- !AbortIfNrc {}
+- !RawUds
+  data: !Bytes 22 f1 90
+"##;
+const EXPECTED_RAWUDS_SUPPRESSED: &[&str] = &["3e 80", "85 82", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn rawuds_suppressed() {
+    let res = common::run_test_scenario_str(RAWUDS_SUPPRESSED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_RAWUDS_SUPPRESSED)));
+}
+
+const RAWUDS_SUPPRESSED_NRC: &str = r##"
+- !RawUds
+  data: !Bytes 85 81
+- !AbortIfNrc
+  nrc: 0x11
+- !RawUds
+  data: !Bytes 22 f1 90
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn rawuds_suppressed_nrc() {
+    let res =
+        common::run_test_scenario_str_outcome(RAWUDS_SUPPRESSED_NRC, Settings::default()).await;
+    assert!(matches!(res, Ok(Outcome::Aborted)));
+}
@@ -0,0 +1,72 @@
+use super::common;
+use crate::scenario::{error::ScenarioError, main::Settings, parser};
+
+const READDTC: &str = r"
+- !ReadDTC
+  report: !NumberByStatusMask 0x08
+- !ReadDTC
+  report: !ByStatusMask 0x08
+- !ReadDTC
+  report: !Snapshot
+    dtc: 0x012345
+    record: 0xff
+- !ReadDTC
+  report: !ExtendedData
+    dtc: 0x012345
+    record: 0x01
+- !ReadDTC
+  report: Supported
+- !ClearDTC {}
+";
+const EXPECTED_READDTC: &[&str] = &[
+    "19 01 08",
+    "19 02 08",
+    "19 04 01 23 45 ff",
+    "19 06 01 23 45 01",
+    "19 0a",
+    "14 ff ff ff",
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn readdtc() {
+    let res = common::run_test_scenario_str(READDTC).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_READDTC)));
+}
+
+const CLEARDTC_GROUP: &str = r"
+- !ClearDTC
+  group: 0x000100
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn cleardtc_group() {
+    let res = common::run_test_scenario_str_outcome(CLEARDTC_GROUP, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x31))));
+}
+
+const CLEARDTC_INVALID_GROUP: &str = r"
+- !ClearDTC
+  group: 0x1000000
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn cleardtc_invalid_group() {
+    let (res, received) = common::run_test_scenario_str_received(CLEARDTC_INVALID_GROUP).await;
+    assert!(
+        matches!(res, Err(ScenarioError::InvalidStep(step, _)) if step == "ClearDTC"),
+        "{res:?}"
+    );
+    assert!(received.is_empty());
+}
+
+const READDTC_INVALID_DTC: &str = r"
+- !ReadDTC
+  report: !Snapshot
+    dtc: 0x1012345
+    record: 0xff
+";
+
+#[test]
+fn readdtc_invalid_dtc() {
+    assert!(parser::read_scenario_str(READDTC_INVALID_DTC).is_err());
+}
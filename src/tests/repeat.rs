@@ -0,0 +1,21 @@
+use super::common;
+
+const REPEAT: &str = r##"
+- !EvalExpr
+  expression: request = (0x22, 0xf1, 0x90);
+- !Repeat
+  count: 3
+  variable: i
+  steps:
+  - !EvalExpr
+    expression: request = (0x22, 0xf1, 0x90 + i);
+  - !RawUds
+    data: !EvalExprVarname request
+"##;
+const EXPECTED_REPEAT: &[&str] = &["22 f1 90", "22 f1 91", "22 f1 92"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn repeat() {
+    let res = common::run_test_scenario_str(REPEAT).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_REPEAT)));
+}
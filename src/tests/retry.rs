@@ -0,0 +1,118 @@
+use super::common;
+use crate::scenario::parser;
+
+const RETRY_SUCCESS: &str = r##"
+- !EvalExpr
+  expression: attempt = 0;
+- !Retry
+  max_attempts: 3
+  delay_ms: 10
+  backoff_factor: 2
+  steps:
+  - !EvalExpr
+    expression: attempt = attempt + 1;
+  - !If
+    condition: attempt < 2
+    then:
+    - !RawUds
+      data: !Bytes 22 ff ff
+    else:
+    - !ReadDID
+      did: 0xf190
+- !ReadDID
+  did: 0xf012
+"##;
+const EXPECTED_RETRY_SUCCESS: &[&str] = &["22 ff ff", "22 f1 90", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn retry_success() {
+    let res = common::run_test_scenario_str(RETRY_SUCCESS).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_RETRY_SUCCESS)));
+}
+
+const RETRY_EXHAUSTED: &str = r##"
+- !Try
+  steps:
+  - !Retry
+    max_attempts: 3
+    delay_ms: 10
+    nrcs: [0x10]
+    steps:
+    - !RawUds
+      data: !Bytes 22 ff ff
+  on_error:
+  - !If
+    condition: error_nrc == 0x10
+    then:
+    - !ReadDID
+      did: 0xf190
+"##;
+const EXPECTED_RETRY_EXHAUSTED: &[&str] = &["22 ff ff", "22 ff ff", "22 ff ff", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn retry_exhausted() {
+    let res = common::run_test_scenario_str(RETRY_EXHAUSTED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_RETRY_EXHAUSTED)));
+}
+
+const RETRY_NOT_RETRYABLE: &str = r##"
+- !Try
+  steps:
+  - !Retry
+    max_attempts: 3
+    delay_ms: 10
+    nrcs: [0x21, 0x22]
+    steps:
+    - !RawUds
+      data: !Bytes 22 ff ff
+  on_error:
+  - !ReadDID
+    did: 0xf190
+"##;
+const EXPECTED_RETRY_NOT_RETRYABLE: &[&str] = &["22 ff ff", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn retry_not_retryable() {
+    let res = common::run_test_scenario_str(RETRY_NOT_RETRYABLE).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_RETRY_NOT_RETRYABLE)));
+}
+
+const RETRY_NRC_NOT_RETRYABLE_KIND: &str = r##"
+- !Try
+  steps:
+  - !Retry
+    max_attempts: 3
+    delay_ms: 10
+    nrcs: [0x10]
+    errors: [Timeout]
+    steps:
+    - !RawUds
+      data: !Bytes 22 ff ff
+  on_error:
+  - !ReadDID
+    did: 0xf190
+"##;
+const EXPECTED_RETRY_NRC_NOT_RETRYABLE_KIND: &[&str] = &["22 ff ff", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn retry_nrc_not_retryable_kind() {
+    let res = common::run_test_scenario_str(RETRY_NRC_NOT_RETRYABLE_KIND).await;
+    assert_eq!(
+        res,
+        Ok(common::uds_seq(EXPECTED_RETRY_NRC_NOT_RETRYABLE_KIND))
+    );
+}
+
+const RETRY_NO_ATTEMPT: &str = r##"
+- !Retry
+  max_attempts: 0
+  delay_ms: 10
+  steps:
+  - !ReadDID
+    did: 0xf190
+"##;
+
+#[test]
+fn retry_no_attempt() {
+    assert!(parser::read_scenario_str(RETRY_NO_ATTEMPT).is_err());
+}
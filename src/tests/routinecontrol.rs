@@ -0,0 +1,116 @@
+use super::common;
+use crate::scenario::{error::ScenarioError, main::Settings};
+
+const ROUTINECONTROL: &str = r"
+- !EvalExpr
+  expression: polls = 0
+- !RoutineControl
+  control: Start
+  routine: 0xff00
+  option: !Bytes 44 00 00 40 00
+  poll_until: polls = polls + 1; reply_nth(4) == 1 && polls == 3
+  poll_interval_ms: 10
+- !RoutineControl
+  control: Stop
+  routine: 0xff00
+";
+const EXPECTED_ROUTINECONTROL: &[&str] = &[
+    "31 01 ff 00 44 00 00 40 00",
+    "31 03 ff 00",
+    "31 03 ff 00",
+    "31 02 ff 00",
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn routinecontrol() {
+    let res = common::run_test_scenario_str(ROUTINECONTROL).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_ROUTINECONTROL)));
+}
+
+const ROUTINECONTROL_POLL_TIMEOUT: &str = r"
+- !RoutineControl
+  control: Start
+  routine: 0xff00
+  poll_until: reply_nth(4) == 0
+  poll_interval_ms: 10
+  poll_timeout_ms: 100
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn routinecontrol_poll_timeout() {
+    let res =
+        common::run_test_scenario_str_outcome(ROUTINECONTROL_POLL_TIMEOUT, Settings::default())
+            .await;
+    assert!(matches!(res, Err(ScenarioError::Timeout(step)) if step == "RoutineControl at step 1"));
+}
+
+const ROUTINECONTROL_START_DONE: &str = r"
+- !RoutineControl
+  control: Start
+  routine: 0xff00
+  poll_until: reply_nth(4) == 1
+  poll_interval_ms: 10
+";
+const EXPECTED_ROUTINECONTROL_START_DONE: &[&str] = &["31 01 ff 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn routinecontrol_start_done() {
+    let res = common::run_test_scenario_str(ROUTINECONTROL_START_DONE).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_ROUTINECONTROL_START_DONE)));
+}
+
+// The condition is only checked on the results, the reply to the start being
+// suppressed.
+const ROUTINECONTROL_SUPPRESSED: &str = r"
+- !RoutineControl
+  control: Start
+  routine: 0xff00
+  suppress_response: true
+  poll_until: reply_nth(4) == 1
+  poll_interval_ms: 10
+";
+const EXPECTED_ROUTINECONTROL_SUPPRESSED: &[&str] = &["31 81 ff 00", "31 03 ff 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn routinecontrol_suppressed() {
+    let res = common::run_test_scenario_str(ROUTINECONTROL_SUPPRESSED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_ROUTINECONTROL_SUPPRESSED)));
+}
+
+// The poll interval isn't shorter than the timeout, a single poll is sent at
+// the deadline
+const ROUTINECONTROL_POLL_DEADLINE: &str = r"
+- !RoutineControl
+  control: Start
+  routine: 0xff00
+  poll_until: reply_nth(4) == 0
+  poll_interval_ms: 100
+  poll_timeout_ms: 100
+";
+const EXPECTED_ROUTINECONTROL_POLL_DEADLINE: &[&str] = &["31 01 ff 00", "31 03 ff 00"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn routinecontrol_poll_deadline() {
+    let (res, received) =
+        common::run_test_scenario_str_received(ROUTINECONTROL_POLL_DEADLINE).await;
+    assert!(
+        matches!(&res, Err(ScenarioError::Timeout(step)) if step == "RoutineControl at step 1"),
+        "{res:?}"
+    );
+    assert_eq!(
+        received,
+        common::uds_seq(EXPECTED_ROUTINECONTROL_POLL_DEADLINE)
+    );
+}
+
+const ROUTINECONTROL_BUSY: &str = r"
+- !RoutineControl
+  control: RequestResults
+  routine: 0xff01
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn routinecontrol_busy() {
+    let res = common::run_test_scenario_str_outcome(ROUTINECONTROL_BUSY, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x21))));
+}
@@ -0,0 +1,212 @@
+use std::env::{self, consts::DLL_PREFIX, consts::DLL_SUFFIX};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::OnceLock;
+
+use super::common;
+use crate::scenario::{error::ScenarioError, main::Settings};
+
+const SECURITYACCESS_STDIN: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Command
+    program: sh
+    args: [-c, "read seed; printf '%08x' $((0x$seed ^ 0xffffffff))"]
+"##;
+const SECURITYACCESS_ARGUMENT: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Command
+    program: sh
+    args: [-c, "printf '%08x' $((0x$0 ^ 0xffffffff))"]
+    seed: Argument
+"##;
+const EXPECTED_SECURITYACCESS: &[&str] = &["27 01", "27 02 ed cb a9 87"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_stdin() {
+    let res = common::run_test_scenario_str(SECURITYACCESS_STDIN).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_SECURITYACCESS)));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_argument() {
+    let res = common::run_test_scenario_str(SECURITYACCESS_ARGUMENT).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_SECURITYACCESS)));
+}
+
+const SECURITYACCESS_STDIN_UNREAD: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Command
+    program: echo
+    args: [edcba987]
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_stdin_unread() {
+    let res = common::run_test_scenario_str(SECURITYACCESS_STDIN_UNREAD).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_SECURITYACCESS)));
+}
+
+const SECURITYACCESS_UNLOCKED: &str = r##"
+- !SecurityAccess
+  level: 0x03
+  key: !Command
+    program: false
+"##;
+const EXPECTED_SECURITYACCESS_UNLOCKED: &[&str] = &["27 03"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_unlocked() {
+    let res = common::run_test_scenario_str(SECURITYACCESS_UNLOCKED).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_SECURITYACCESS_UNLOCKED)));
+}
+
+const SECURITYACCESS_DELAY: &str = r##"
+- !SecurityAccess
+  level: 0x05
+  key: !Command
+    program: false
+  max_attempts: 2
+  delay_ms: 10
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_delay() {
+    let res =
+        common::run_test_scenario_str_outcome(SECURITYACCESS_DELAY, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x37))));
+}
+
+const SECURITYACCESS_INVALID_KEY: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Command
+    program: echo
+    args: ["00 00 00 00"]
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_invalid_key() {
+    let res =
+        common::run_test_scenario_str_outcome(SECURITYACCESS_INVALID_KEY, Settings::default())
+            .await;
+    assert!(matches!(res, Err(ScenarioError::Nrc(0x35))));
+}
+
+const SECURITYACCESS_FAILING_COMMAND: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Command
+    program: false
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_failing_command() {
+    let res =
+        common::run_test_scenario_str_outcome(SECURITYACCESS_FAILING_COMMAND, Settings::default())
+            .await;
+    assert!(matches!(res, Err(ScenarioError::SeedKey(_))));
+}
+
+const SECURITYACCESS_PLUGIN: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Plugin
+    variant: ECU1
+    key_length: 4
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_plugin_not_configured() {
+    let res =
+        common::run_test_scenario_str_outcome(SECURITYACCESS_PLUGIN, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::SeedKey(_))));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_plugin_missing_library() {
+    let settings = Settings {
+        seedkey_library: Some("/nonexistent/libseedkey.so".into()),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_outcome(SECURITYACCESS_PLUGIN, settings).await;
+    assert!(matches!(res, Err(ScenarioError::SeedKey(_))));
+}
+
+/// Build the seed-key library of the tests once, into the temporary directory.
+fn fixture_library() -> &'static Path {
+    static LIBRARY: OnceLock<PathBuf> = OnceLock::new();
+    LIBRARY.get_or_init(|| {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/fixtures/seedkey.rs");
+        let library = env::temp_dir().join(format!(
+            "{DLL_PREFIX}seedkey_fixture_{}{DLL_SUFFIX}",
+            process::id()
+        ));
+        let status = Command::new("rustc")
+            .args(["--crate-type", "cdylib", "--edition", "2021", "-O"])
+            .args(["--crate-name", "seedkey_fixture", "-o"])
+            .arg(&library)
+            .arg(&source)
+            .status()
+            .expect("rustc can't be run");
+        assert!(status.success(), "{} can't be built", source.display());
+        library
+    })
+}
+
+fn fixture_settings() -> Settings {
+    Settings {
+        seedkey_library: Some(fixture_library().into()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_plugin() {
+    let res =
+        common::run_test_scenario_str_with_settings(SECURITYACCESS_PLUGIN, fixture_settings())
+            .await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_SECURITYACCESS)));
+}
+
+const SECURITYACCESS_PLUGIN_KEY_LENGTH: &str = r##"
+- !SecurityAccess
+  level: 0x01
+  key: !Plugin
+    variant: SHORT
+    key_length: 4
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_plugin_key_length() {
+    let res =
+        common::run_test_scenario_str_outcome(SECURITYACCESS_PLUGIN_KEY_LENGTH, fixture_settings())
+            .await;
+    assert!(matches!(res, Err(ScenarioError::SeedKey(_))));
+}
+
+const SECURITYACCESS_GENERATE_KEY: &str = r##"
+- !RawUds
+  data: !Bytes 27 01
+- !EvalExpr
+  expression: |
+    seed = (reply_nth(2), reply_nth(3), reply_nth(4), reply_nth(5));
+    request = (0x27, 0x02, generate_key(seed, 1, "ECU1"));
+- !RawUds
+  data: !EvalExprVarname request
+- !ExpectReply
+  starts_with: !Bytes 67 02
+  on_failure: Abort
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn securityaccess_generate_key() {
+    let res = common::run_test_scenario_str_with_settings(
+        SECURITYACCESS_GENERATE_KEY,
+        fixture_settings(),
+    )
+    .await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_SECURITYACCESS)));
+}
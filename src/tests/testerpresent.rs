@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use super::common;
+use crate::scenario::main::{Outcome, Settings};
+
+const TESTERPRESENT: &str = r##"
+- !TesterPresent
+  period_ms: 20
+- !SleepMs 110
+- !ReadDID
+  did: 0xf190
+- !ExpectReply
+  starts_with: !Bytes 62 f1 90
+  on_failure: Abort
+- !TesterPresent
+  stop: true
+- !SleepMs 60
+- !ReadDID
+  did: 0xf190
+"##;
+const EXPECTED_TESTERPRESENT: &[&str] = &["22 f1 90", "22 f1 90"];
+
+fn split_tester_present(received: Vec<Vec<u8>>) -> (usize, Vec<Vec<u8>>) {
+    let (tester_present, others): (Vec<Vec<u8>>, Vec<Vec<u8>>) = received
+        .into_iter()
+        .partition(|req| req[..] == [0x3e, 0x80]);
+    (tester_present.len(), others)
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn testerpresent() {
+    let received = common::run_test_scenario_str(TESTERPRESENT).await.unwrap();
+    let (nb_tester_present, others) = split_tester_present(received);
+    assert!(
+        nb_tester_present >= 3,
+        "only {nb_tester_present} TesterPresent"
+    );
+    assert_eq!(others, common::uds_seq(EXPECTED_TESTERPRESENT));
+}
+
+const TESTERPRESENT_AUTO: &str = r##"
+- !SleepMs 60
+- !DiagnosticSession
+  session: Extended
+- !SleepMs 110
+- !DiagnosticSession
+  session: Default
+- !SleepMs 60
+"##;
+const EXPECTED_TESTERPRESENT_AUTO: &[&str] = &["10 03", "10 01"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn testerpresent_auto() {
+    let settings = Settings {
+        tester_present: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    let received = common::run_test_scenario_str_with_settings(TESTERPRESENT_AUTO, settings)
+        .await
+        .unwrap();
+    assert_eq!(received.first(), Some(&vec![0x10, 0x03]));
+    assert_eq!(received.last(), Some(&vec![0x10, 0x01]));
+    let (nb_tester_present, others) = split_tester_present(received);
+    assert!(
+        nb_tester_present >= 3,
+        "only {nb_tester_present} TesterPresent"
+    );
+    assert_eq!(others, common::uds_seq(EXPECTED_TESTERPRESENT_AUTO));
+}
+
+// The ECU stays silent on the TesterPresent of the keep-alive, the NRC to the
+// TesterPresent of the scenario is for the scenario.
+const TESTERPRESENT_SCENARIO_NRC: &str = r##"
+- !TesterPresent
+  period_ms: 20
+- !SleepMs 110
+- !RawUds
+  data: !Bytes 3e 80 00
+- !AbortIfNrc
+  nrc: 0x11
+- !ReadDID
+  did: 0xf190
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn testerpresent_scenario_nrc() {
+    let res =
+        common::run_test_scenario_str_outcome(TESTERPRESENT_SCENARIO_NRC, Settings::default())
+            .await;
+    assert!(matches!(res, Ok(Outcome::Aborted)));
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use super::common;
+use crate::scenario::{
+    error::ScenarioError,
+    main::{Outcome, Settings},
+};
+
+const STEP_TIMEOUT: &str = r"
+- !RawUds
+  data: !Bytes 31 01 ff ff
+  timeout_ms: 100
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn step_timeout() {
+    let res = common::run_test_scenario_str_outcome(STEP_TIMEOUT, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::Timeout(step)) if step == "RawUds at step 1"));
+}
+
+const NESTED_TIMEOUT: &str = r"
+- !Repeat
+  count: 3
+  timeout_ms: 100
+  steps:
+  - !RawUds
+    data: !Bytes 31 01 ff ff
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn nested_timeout() {
+    let res = common::run_test_scenario_str_outcome(NESTED_TIMEOUT, Settings::default()).await;
+    assert!(matches!(res, Err(ScenarioError::Timeout(step)) if step == "Repeat at step 1"));
+}
+
+const CAUGHT_TIMEOUT: &str = r#"
+- !Try
+  steps:
+  - !RawUds
+    data: !Bytes 31 01 ff ff
+    timeout_ms: 100
+  on_error:
+  - !EvalExpr
+    expression: timed_out = error_kind == "Timeout";
+- !ReadDID
+  did: 0xf190
+- !ExpectReply
+  starts_with: !Bytes 62 f1 90
+- !If
+  condition: timed_out
+  then:
+  - !RawUds
+    data: !Bytes 22 f0 12
+"#;
+const EXPECTED_CAUGHT_TIMEOUT: &[&str] = &["31 01 ff ff", "22 f1 90", "22 f0 12"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn caught_timeout() {
+    let res = common::run_test_scenario_str(CAUGHT_TIMEOUT).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_CAUGHT_TIMEOUT)));
+    let res = common::run_test_scenario_str_outcome(CAUGHT_TIMEOUT, Settings::default()).await;
+    assert!(matches!(res, Ok(Outcome::Success)));
+}
+
+const SCENARIO_TIMEOUT: &str = r"
+- !ReadDID
+  did: 0xf190
+- !SleepMs 1000
+- !ReadDID
+  did: 0xf190
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn scenario_timeout() {
+    let settings = Settings {
+        timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
+    };
+    let res = common::run_test_scenario_str_outcome(SCENARIO_TIMEOUT, settings).await;
+    assert!(matches!(res, Err(ScenarioError::Timeout(step)) if step == "SleepMs at step 2"));
+}
+
+const PROCEDURE_TIMEOUT: &str = r"
+procedures:
+  start_routine:
+    steps:
+    - !ReadDID
+      did: 0xf190
+    - !RawUds
+      data: !Bytes 31 01 ff ff
+      timeout_ms: 100
+steps:
+- !ReadDID
+  did: 0xf190
+- !Call
+  procedure: start_routine
+";
+
+#[tokio::test(flavor = "current_thread")]
+async fn procedure_timeout() {
+    let res = common::run_test_scenario_str_outcome(PROCEDURE_TIMEOUT, Settings::default()).await;
+    assert!(
+        matches!(res, Err(ScenarioError::Timeout(step)) if step == "RawUds at step 2, procedure start_routine, step 2")
+    );
+}
@@ -0,0 +1,92 @@
+use super::common;
+use crate::scenario::error::ScenarioError;
+
+const TRANSFERUPLOAD: &str = r##"
+- !TransferUpload
+  compression_method: 0
+  encrypt_method: 0
+  addr: 0x4000
+  memorysize: 6
+  filename: /tmp/upload.bin
+  variable: dump
+- !RawUds
+  data: !EvalExprVarname dump
+"##;
+const EXPECTED_TRANSFERUPLOAD: &[&str] = &[
+    "35 00 44 00 00 40 00 00 00 00 06", // RequestUpload
+    "36 01",                            // TransferData
+    "36 02",                            // TransferData
+    "37",                               // TransferExit
+    "de ad ba be ca fe",                // Uploaded data
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn transferupload() {
+    let res = common::run_test_scenario_str(TRANSFERUPLOAD).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_TRANSFERUPLOAD)));
+    let uploaded = std::fs::read("/tmp/upload.bin").unwrap();
+    assert_eq!(uploaded, vec![0xde, 0xad, 0xba, 0xbe, 0xca, 0xfe]);
+}
+
+const TRANSFERUPLOAD_FORMAT: &str = r##"
+- !TransferUpload
+  compression_method: 0
+  encrypt_method: 0
+  addr: 0x4000
+  memorysize: 6
+  address_bytes: 2
+  size_bytes: 1
+"##;
+const EXPECTED_TRANSFERUPLOAD_FORMAT: &[&str] = &["35 00 12 40 00 06", "36 01", "36 02", "37"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn transferupload_format() {
+    let res = common::run_test_scenario_str(TRANSFERUPLOAD_FORMAT).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_TRANSFERUPLOAD_FORMAT)));
+}
+
+const TRANSFERUPLOAD_ADDRESS_OVERFLOW: &str = r##"
+- !TransferUpload
+  compression_method: 0
+  encrypt_method: 0
+  addr: 0x100000000
+  memorysize: 6
+"##;
+
+#[tokio::test(flavor = "current_thread")]
+async fn transferupload_address_overflow() {
+    let (res, received) =
+        common::run_test_scenario_str_received(TRANSFERUPLOAD_ADDRESS_OVERFLOW).await;
+    assert!(
+        matches!(res, Err(ScenarioError::InvalidStep(step, _)) if step == "TransferUpload"),
+        "{res:?}"
+    );
+    assert!(received.is_empty());
+}
+
+// The ECU ends the upload with an empty block, before memorysize bytes.
+const TRANSFERUPLOAD_SHORT: &str = r##"
+- !TransferUpload
+  compression_method: 0
+  encrypt_method: 0
+  addr: 0x4000
+  memorysize: 8
+  variable: dump
+"##;
+const EXPECTED_TRANSFERUPLOAD_SHORT: &[&str] = &[
+    "35 00 44 00 00 40 00 00 00 00 08",
+    "36 01",
+    "36 02",
+    "36 03",
+    "37",
+];
+
+#[tokio::test(flavor = "current_thread")]
+async fn transferupload_short() {
+    let (res, received) = common::run_test_scenario_str_received(TRANSFERUPLOAD_SHORT).await;
+    assert!(
+        matches!(res, Err(ScenarioError::UnexpectedUdsMessage(_))),
+        "{res:?}"
+    );
+    assert_eq!(received, common::uds_seq(EXPECTED_TRANSFERUPLOAD_SHORT));
+}
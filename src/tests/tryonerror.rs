@@ -0,0 +1,56 @@
+use super::common;
+use crate::scenario::error::ScenarioError;
+
+const TRY_ON_ERROR: &str = r##"
+- !Try
+  steps:
+  - !ReadDID
+    did: 0xf190
+  - !TransferDownload
+    compression_method: 1
+    encrypt_method: 2
+    addr: 19
+    filename: /nonexistent/FD01.bin
+    memorysize: 4
+  - !ReadDID
+    did: 0xf012
+  on_error:
+  - !If
+    condition: error_kind == "Io" && error_nrc == 0
+    then:
+    - !RawUds
+      data: !Bytes 10 01
+  finally:
+  - !ReadDID
+    did: 0xf191
+- !ReadDID
+  did: 0xf190
+"##;
+const EXPECTED_TRY_ON_ERROR: &[&str] = &["22 f1 90", "10 01", "22 f1 91", "22 f1 90"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn try_on_error() {
+    let res = common::run_test_scenario_str(TRY_ON_ERROR).await;
+    assert_eq!(res, Ok(common::uds_seq(EXPECTED_TRY_ON_ERROR)));
+}
+
+const TRY_FINALLY: &str = r##"
+- !Try
+  steps:
+  - !EvalExpr
+    expression: a = undefined_variable + 1;
+  finally:
+  - !ReadDID
+    did: 0xf191
+- !ReadDID
+  did: 0xf190
+"##;
+
+const EXPECTED_TRY_FINALLY: &[&str] = &["22 f1 91"];
+
+#[tokio::test(flavor = "current_thread")]
+async fn try_finally() {
+    let (res, received) = common::run_test_scenario_str_received(TRY_FINALLY).await;
+    assert!(matches!(res, Err(ScenarioError::EvalExpr(_, _))));
+    assert_eq!(received, common::uds_seq(EXPECTED_TRY_FINALLY));
+}